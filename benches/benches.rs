@@ -2,11 +2,13 @@ use criterion::criterion_main;
 
 mod board;
 mod conversions;
+mod mcts_search;
 mod mean_max_search;
 // TODO: Add benchmarking for heuristic.
 
 criterion_main!(
     board::board,
     mean_max_search::mean_max_search,
+    mcts_search::mcts_search,
     conversions::bench_conversions,
 );
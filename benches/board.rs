@@ -1,8 +1,10 @@
 use criterion::{criterion_group, BenchmarkId, Criterion, Throughput};
 use rand::seq::IndexedRandom;
-use rust_2048_solver::game::twenty_forty_eight::State;
+use rand::SeedableRng as _;
+use rust_2048_solver::game::twenty_forty_eight::{bitboard, State};
 use rust_2048_solver::game::{Discrete, GameState, Outcome};
 use std::hash::{self, Hash as _};
+use std::hint;
 
 fn generate_states(count: usize) -> Vec<State<4, 4>> {
     #[rustfmt::skip]
@@ -14,12 +16,12 @@ fn generate_states(count: usize) -> Vec<State<4, 4>> {
     ]);
 
     let mut states = vec![starting_state];
-    let mut rng = rand::rng();
+    let mut rng = rand::rngs::StdRng::seed_from_u64(0);
     while states.len() < count {
         let state = states.choose(&mut rng).unwrap().clone();
 
-        for action in <State<4, 4> as GameState>::Action::iter() {
-            let (_reward, outcome) = state.clone().outcome(action);
+        for action in state.legal_actions() {
+            let (_reward, outcome) = state.clone().outcome(action).unwrap();
             states.extend(outcome.into_iter().map(|weighted| weighted.value));
         }
     }
@@ -46,6 +48,19 @@ fn bench_hash(c: &mut Criterion) {
                 b.iter(|| states.iter().for_each(|state| state.hash(&mut hasher)));
             },
         );
+
+        let states: Vec<_> = states
+            .into_iter()
+            .map(|state| bitboard::State::try_from(state).unwrap())
+            .collect();
+
+        group.bench_with_input(
+            BenchmarkId::new("hash_bitboard_states", num_states),
+            &states,
+            |b, states| {
+                b.iter(|| states.iter().for_each(|state| state.hash(&mut hasher)));
+            },
+        );
     }
 }
 
@@ -55,8 +70,9 @@ fn bench_board_swipe(c: &mut Criterion) {
     c.bench_function("updates", |b| {
         b.iter(|| {
             for action in <State<4, 4> as GameState>::Action::iter() {
-                let (_reward, outcome) = state.clone().outcome(action);
-                state = outcome.collapse();
+                if let Ok((_reward, outcome)) = state.clone().outcome(action) {
+                    state = outcome.collapse();
+                }
             }
 
             if state.is_terminal() {
@@ -66,10 +82,77 @@ fn bench_board_swipe(c: &mut Criterion) {
     });
 }
 
+fn bench_bitboard_swipe(c: &mut Criterion) {
+    let mut state = bitboard::State::new();
+
+    c.bench_function("bitboard updates", |b| {
+        b.iter(|| {
+            for action in <bitboard::State as GameState>::Action::iter() {
+                if let Ok((_reward, outcome)) = state.outcome(action) {
+                    state = outcome.collapse();
+                }
+            }
+
+            if state.is_terminal() {
+                state = bitboard::State::new();
+            }
+        })
+    });
+}
+
+fn bench_swipe_directions(c: &mut Criterion) {
+    use rust_2048_solver::game::twenty_forty_eight::board::{fast_swipe, Cells, Direction};
+
+    /// Swipes the rows (or columns) one at a time, like boards of other sizes.
+    fn swipe_rows(cells: Cells<4, 4>, direction: Direction) -> Cells<4, 4> {
+        let transpose = matches!(direction, Direction::Up | Direction::Down);
+        let mut lines = if transpose { cells.transposed() } else { cells };
+        for line in lines.iter_mut() {
+            match direction {
+                Direction::Left | Direction::Up => fast_swipe::swipe_left(line),
+                Direction::Right | Direction::Down => fast_swipe::swipe_right(line),
+            };
+        }
+
+        if transpose {
+            lines.transposed()
+        } else {
+            lines
+        }
+    }
+
+    let boards: Vec<_> = generate_states(1024)
+        .into_iter()
+        .map(|state| state.cells)
+        .collect();
+    let mut group = c.benchmark_group("swipe 4x4");
+    group.throughput(Throughput::Elements(boards.len().try_into().unwrap()));
+
+    for direction in Direction::iter() {
+        group.bench_with_input(BenchmarkId::new("simd", direction), &boards, |b, boards| {
+            b.iter(|| {
+                for &cells in boards {
+                    let mut cells = cells;
+                    hint::black_box(cells.swipe_simd(direction));
+                    hint::black_box(cells);
+                }
+            })
+        });
+
+        group.bench_with_input(BenchmarkId::new("rows", direction), &boards, |b, boards| {
+            b.iter(|| {
+                for &cells in boards {
+                    hint::black_box(swipe_rows(cells, direction));
+                }
+            })
+        });
+    }
+}
+
 criterion_group!(
     name = board;
     config = Criterion::default()
         .significance_level(0.01);
 
-    targets = bench_hash, bench_board_swipe
+    targets = bench_hash, bench_board_swipe, bench_bitboard_swipe, bench_swipe_directions
 );
@@ -0,0 +1,115 @@
+use criterion::{criterion_group, BenchmarkId, Criterion};
+use rand::SeedableRng as _;
+use rust_2048_solver::bots::mcts::{GreedyRollout, Mcts, RandomRollout};
+use rust_2048_solver::bots::mean_max::searcher::{Decision, SearchConstraint};
+use rust_2048_solver::bots::mean_max::MeanMax;
+use rust_2048_solver::bots::Bot;
+use rust_2048_solver::game::twenty_forty_eight::State;
+use rust_2048_solver::game::{GameState as _, Outcome as _};
+use std::time::{Duration, Instant};
+
+pub fn bench_mcts_search(c: &mut Criterion) {
+    const ITERATIONS: usize = 1000;
+
+    let states = [
+        State::from_cells([[0, 0, 0, 0], [0, 0, 0, 0], [0, 1, 0, 0], [0, 0, 0, 0]]),
+        State::from_cells([[3, 3, 1, 1], [1, 0, 5, 0], [0, 2, 7, 4], [6, 1, 6, 8]]),
+        State::from_cells([[3, 4, 6, 10], [2, 10, 3, 1], [0, 1, 7, 3], [0, 0, 2, 8]]),
+    ];
+
+    for state in states {
+        let parameter_display = format!("{:032x}-{ITERATIONS}", state.cells.as_u128());
+
+        c.bench_with_input(
+            BenchmarkId::new("random-rollout", &parameter_display),
+            &state,
+            |b, state| {
+                let mut ai = Mcts::new(RandomRollout).with_iterations(ITERATIONS);
+                b.iter(|| ai.decide_until(state, SearchConstraint::new()))
+            },
+        );
+
+        c.bench_with_input(
+            BenchmarkId::new("greedy-rollout", &parameter_display),
+            &state,
+            |b, state| {
+                let mut ai = Mcts::new(GreedyRollout).with_iterations(ITERATIONS);
+                b.iter(|| ai.decide_until(state, SearchConstraint::new()))
+            },
+        );
+    }
+}
+
+/// Plays up to `moves` moves of the game of `seed` with `budget` per move, returns the score.
+fn play(bot: &mut dyn Bot<State<4, 4>>, seed: u64, budget: Duration, moves: usize) -> f32 {
+    let mut rng = rand::rngs::StdRng::seed_from_u64(seed);
+    let mut state = State::<4, 4>::new_with_rng(&mut rng);
+    let mut score = 0.0;
+
+    for _ in 0..moves {
+        let constraint = SearchConstraint::new().with_deadline(Instant::now() + budget);
+        let Decision::Act(act) = bot.decide_until(&state, constraint) else {
+            break;
+        };
+
+        let (reward, outcome) = state.outcome(act.action).unwrap();
+        score += reward;
+        state = outcome.collapse_with(&mut rng);
+    }
+
+    score
+}
+
+/// Compares the bots given the same time per move, the benchmark measures how well they keep
+/// to the deadline and the printed score how well they play with it.
+pub fn bench_equal_time(c: &mut Criterion) {
+    const SEEDS: u64 = 2;
+    const MOVES: usize = 1000;
+
+    let state = State::from_cells([[3, 3, 1, 1], [1, 0, 5, 0], [0, 2, 7, 4], [6, 1, 6, 8]]);
+
+    for budget in [Duration::from_millis(1), Duration::from_millis(5)] {
+        let bots: [(&str, Box<dyn Bot<State<4, 4>>>); 3] = [
+            ("mean-max", Box::new(MeanMax::new())),
+            (
+                "mcts-random",
+                Box::new(Mcts::new(RandomRollout).with_seed(0)),
+            ),
+            (
+                "mcts-greedy",
+                Box::new(Mcts::new(GreedyRollout).with_seed(0)),
+            ),
+        ];
+
+        for (name, mut bot) in bots {
+            let score: f32 = (0..SEEDS)
+                .map(|seed| play(bot.as_mut(), seed, budget, MOVES))
+                .sum();
+            println!(
+                "{name} with {budget:?} per move: {:.0} points after {MOVES} moves",
+                score / SEEDS as f32
+            );
+
+            c.bench_with_input(
+                BenchmarkId::new(format!("equal-time-{name}"), format!("{budget:?}")),
+                &budget,
+                |b, &budget| {
+                    b.iter(|| {
+                        let constraint =
+                            SearchConstraint::new().with_deadline(Instant::now() + budget);
+                        bot.decide_until(&state, constraint)
+                    })
+                },
+            );
+        }
+    }
+}
+
+criterion_group!(
+    name = mcts_search;
+    config = Criterion::default()
+        .significance_level(0.01)
+        .measurement_time(std::time::Duration::from_secs(10));
+
+    targets = bench_mcts_search, bench_equal_time
+);
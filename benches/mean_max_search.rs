@@ -1,6 +1,8 @@
 use criterion::{criterion_group, Bencher, BenchmarkId, Criterion};
 use rust_2048_solver::bots::mean_max::{
-    max_depth::MaxDepth as Bound, searcher::SearchConstraint, MeanMax,
+    max_depth::MaxDepth as Bound,
+    searcher::{cache::CacheKind, SearchConstraint},
+    MeanMax, Parallelism,
 };
 use rust_2048_solver::game::twenty_forty_eight::State;
 
@@ -12,7 +14,8 @@ fn run_search<const COLS: usize, const ROWS: usize>(
 
     b.iter_batched(
         MeanMax::new,
-        |mut ai| ai.decide_until(state, search_constraint),
+        // Return the bot so joining its threads isn't measured.
+        |mut ai| (ai.decide_until(state, search_constraint), ai),
         criterion::BatchSize::PerIteration,
     )
 }
@@ -43,6 +46,11 @@ pub fn bench_search_depth(c: &mut Criterion) {
         SearchConstraint::default().with_max_depth(Bound::new(4)),
     );
 
+    bench_search(
+        State::from_cells([[3, 4, 6, 10], [2, 10, 3, 1], [0, 1, 7, 3], [0, 0, 2, 8]]),
+        SearchConstraint::default().with_max_nodes(100_000),
+    );
+
     bench_search(
         State::from_cells([[1, 1, 1, 1], [1, 2, 1, 1], [1, 1, 2, 1], [1, 1, 1, 1]]),
         SearchConstraint::default().with_max_depth(Bound::new(3)),
@@ -59,11 +67,96 @@ pub fn bench_search_depth(c: &mut Criterion) {
     );
 }
 
+pub fn bench_parallelism(c: &mut Criterion) {
+    let inputs = [
+        (
+            State::<4, 4>::from_cells([[3, 3, 1, 1], [1, 0, 5, 0], [0, 2, 7, 4], [6, 1, 6, 8]]),
+            SearchConstraint::default().with_max_depth(Bound::new(3)),
+        ),
+        (
+            State::<4, 4>::from_cells([[3, 4, 6, 10], [2, 10, 3, 1], [0, 1, 7, 3], [0, 0, 2, 8]]),
+            SearchConstraint::default().with_max_depth(Bound::new(4)),
+        ),
+    ];
+
+    for (state, constraint) in inputs {
+        for parallelism in [Parallelism::StaggeredDepths, Parallelism::SplitWork] {
+            for threads in [1, 2, 4, 8] {
+                let parameter_display =
+                    format!("{parallelism:?}-{threads}-{:032x}", state.cells.as_u128());
+
+                c.bench_with_input(
+                    BenchmarkId::new("parallelism", parameter_display),
+                    &(parallelism, threads),
+                    |b, &(parallelism, threads)| {
+                        b.iter_batched(
+                            || {
+                                MeanMax::builder()
+                                    .with_parallelism(parallelism)
+                                    .with_threads(threads)
+                                    .build()
+                                    .unwrap()
+                            },
+                            |mut ai| (ai.decide_until(&state, constraint), ai),
+                            criterion::BatchSize::PerIteration,
+                        )
+                    },
+                );
+            }
+        }
+    }
+}
+
+/// Puts and then gets many more keys than the caches hold, like a long search, spread over the
+/// threads.
+pub fn bench_cache_kind(c: &mut Criterion) {
+    const BYTES: usize = 1 << 20;
+    const KEYS: u64 = 1 << 16;
+
+    // The size of an evaluation, prioritized by exactness, depth and step
+    type Value = [f32; 5];
+    type Priority = (bool, u8, u8);
+
+    // Spread like the hashes of boards
+    let keys: Vec<u64> = (0..KEYS)
+        .map(|key| key.wrapping_mul(0x9e37_79b9_7f4a_7c15))
+        .collect();
+
+    for cache_kind in [CacheKind::Priority, CacheKind::Transposition] {
+        for threads in [1, 4] {
+            let cache = cache_kind.with_byte_budget::<u64, Value, Priority>(BYTES);
+            let chunk = keys.len() / threads;
+
+            c.bench_with_input(
+                BenchmarkId::new("cache", format!("{cache_kind:?}-{threads}")),
+                &keys,
+                |b, keys| {
+                    b.iter(|| {
+                        std::thread::scope(|scope| {
+                            for keys in keys.chunks(chunk) {
+                                let cache = &cache;
+                                scope.spawn(move || {
+                                    for (step, &key) in keys.iter().enumerate() {
+                                        let priority = (key % 4 == 0, (key % 8) as u8, step as u8);
+                                        cache.put(key, [0.0; 5], priority);
+                                    }
+
+                                    keys.iter().filter(|&key| cache.get(key).is_some()).count()
+                                });
+                            }
+                        })
+                    })
+                },
+            );
+        }
+    }
+}
+
 criterion_group!(
     name = mean_max_search;
     config = Criterion::default()
         .significance_level(0.01)
         .measurement_time(std::time::Duration::from_secs(10));
 
-    targets = bench_search_depth
+    targets = bench_search_depth, bench_parallelism, bench_cache_kind
 );
@@ -1,5 +1,5 @@
 use std::fmt::{Display, Write};
-use std::ops::{Add, AddAssign, Div, Mul};
+use std::ops::{Add, AddAssign, Div, Mul, MulAssign};
 
 #[derive(Copy, Clone, Debug)]
 struct Fraction<N, D> {
@@ -103,6 +103,21 @@ impl<N, D> WeightedAverage<N, D> {
     {
         self.0.evaluate()
     }
+
+    /// Sum of the weights of the values.
+    pub fn weight(&self) -> &D {
+        &self.0.denominator
+    }
+
+    /// Scales the weights of the values so far by `factor`, so the next values count more.
+    pub fn decay(&mut self, factor: D)
+    where
+        N: MulAssign<D>,
+        D: MulAssign + Clone,
+    {
+        self.0.numerator *= factor.clone();
+        self.0.denominator *= factor;
+    }
 }
 
 impl<N: num::traits::Zero, D: num::traits::Zero> Default for WeightedAverage<N, D> {
@@ -1,6 +1,6 @@
 use crate::accumulator::Accumulator;
 use crate::game::twenty_forty_eight::board::{Cell, Cells};
-use crate::game::twenty_forty_eight::Outcome;
+use crate::game::twenty_forty_eight::{bitboard, Outcome};
 use std::collections::HashMap;
 
 pub trait Heuristic<T, E> {
@@ -274,10 +274,14 @@ fn base_heuristic<const COLS: usize, const ROWS: usize>(
     // 2_usize.pow((empty_count + 1) as u32) as Eval
     // 1.0
 
-    2_usize.pow((preprocessed_board.count_empty() + 1) as u32) as Eval
+    empty_count_heuristic(preprocessed_board.count_empty())
 }
 
-#[derive(Debug)]
+fn empty_count_heuristic(empty_count: usize) -> Eval {
+    2_usize.pow((empty_count + 1) as u32) as Eval
+}
+
+#[derive(Clone, Debug)]
 pub struct TwentyFortyEightHeuristic<const COLS: usize, const ROWS: usize> {
     accumulator: Accumulator<PreprocessedBoard<COLS, ROWS>, Eval>,
 }
@@ -313,3 +317,18 @@ impl<const ROWS: usize, const COLS: usize> Heuristic<Outcome<COLS, ROWS>, Eval>
         // TODO: training is disabled
     }
 }
+
+impl Heuristic<bitboard::Outcome, Eval> for TwentyFortyEightHeuristic<4, 4> {
+    fn eval(&self, state: &bitboard::Outcome) -> Eval {
+        // Only the learned evaluations are keyed by cells, the base heuristic reads the nibbles.
+        if self.accumulator.memory.is_empty() {
+            return empty_count_heuristic(state.board.count_empty());
+        }
+
+        self.eval(&Outcome::from(*state))
+    }
+
+    fn update(&mut self, _state: bitboard::Outcome, _eval: Eval) {
+        // TODO: training is disabled
+    }
+}
@@ -0,0 +1,402 @@
+//! Monte Carlo tree search with the UCT selection rule.
+//!
+//! Chance nodes are sampled by collapsing the outcome of an action, so every state that was
+//! drawn from it gets its own child. The value of a state is the sum of the rewards that follow
+//! it, estimated with rollouts from the leaves of the tree.
+
+use super::mean_max::max_depth::MaxDepth;
+use super::mean_max::searcher::{Decision, EvaluatedAction, Evaluation, SearchConstraint, Value};
+use crate::game::{self, Outcome as _};
+use rand::seq::IteratorRandom as _;
+use rand::SeedableRng as _;
+use std::time::Instant;
+
+/// Picks the actions played by the rollouts.
+pub trait RolloutPolicy<G: game::GameState> {
+    /// Returns the action to play from `state`, or `None` if there is no legal action.
+    fn choose<R: rand::Rng + ?Sized>(&mut self, state: &G, rng: &mut R) -> Option<G::Action>;
+}
+
+/// Plays uniformly random legal actions.
+#[derive(Clone, Copy, Debug, Default)]
+pub struct RandomRollout;
+
+impl<G> RolloutPolicy<G> for RandomRollout
+where
+    G: game::GameState,
+    G::Action: game::Discrete,
+{
+    fn choose<R: rand::Rng + ?Sized>(&mut self, state: &G, rng: &mut R) -> Option<G::Action> {
+        state.legal_actions().choose(rng)
+    }
+}
+
+/// Plays the legal action with the highest immediate reward.
+#[derive(Clone, Copy, Debug, Default)]
+pub struct GreedyRollout;
+
+impl<G> RolloutPolicy<G> for GreedyRollout
+where
+    G: game::GameState + Clone,
+    G::Action: game::Discrete + Clone,
+    Value: From<G::Reward>,
+{
+    fn choose<R: rand::Rng + ?Sized>(&mut self, state: &G, _rng: &mut R) -> Option<G::Action> {
+        let reward = |action: &G::Action| {
+            let Ok((reward, _outcome)) = state.clone().outcome(action.clone()) else {
+                unreachable!("legal actions should be applicable");
+            };
+            Value::from(reward)
+        };
+
+        state
+            .legal_actions()
+            .max_by(|a, b| reward(a).total_cmp(&reward(b)))
+    }
+}
+
+/// A bot searching a tree of sampled games, grown towards the most promising actions.
+pub struct Mcts<Rollout> {
+    /// Weight of the exploration term of UCT, relative to the value of the parent state.
+    pub exploration: Value,
+    /// Maximum number of actions played by a rollout.
+    pub rollout_depth: usize,
+    /// Number of iterations of a search without a deadline.
+    pub iterations: usize,
+    rollout: Rollout,
+    rng: rand::rngs::StdRng,
+}
+
+impl<Rollout> Mcts<Rollout> {
+    pub fn new(rollout: Rollout) -> Self {
+        Self {
+            exploration: 0.5,
+            rollout_depth: 100,
+            iterations: 1000,
+            rollout,
+            rng: rand::rngs::StdRng::from_os_rng(),
+        }
+    }
+
+    #[must_use]
+    pub fn with_exploration(mut self, exploration: Value) -> Self {
+        self.exploration = exploration;
+        self
+    }
+
+    #[must_use]
+    pub fn with_rollout_depth(mut self, rollout_depth: usize) -> Self {
+        self.rollout_depth = rollout_depth;
+        self
+    }
+
+    #[must_use]
+    pub fn with_iterations(mut self, iterations: usize) -> Self {
+        self.iterations = iterations;
+        self
+    }
+
+    /// Makes the searches reproducible.
+    #[must_use]
+    pub fn with_seed(mut self, seed: u64) -> Self {
+        self.rng = rand::rngs::StdRng::seed_from_u64(seed);
+        self
+    }
+}
+
+impl Default for Mcts<RandomRollout> {
+    fn default() -> Self {
+        Self::new(RandomRollout)
+    }
+}
+
+/// Statistics of the returns that went through a node or an edge.
+#[derive(Clone, Copy, Debug, Default)]
+struct Returns {
+    visits: u32,
+    sum: Value,
+    squares: Value,
+}
+
+impl Returns {
+    fn add(&mut self, value: Value) {
+        self.visits += 1;
+        self.sum += value;
+        self.squares += value * value;
+    }
+
+    fn mean(&self) -> Value {
+        self.sum / self.visits as Value
+    }
+
+    /// Variance of the mean of the returns.
+    fn variance(&self) -> Value {
+        if self.visits < 2 {
+            return 0.0;
+        }
+
+        let visits = self.visits as Value;
+        let sample_variance = (self.squares - self.sum * self.mean()) / (visits - 1.0);
+        sample_variance.max(0.0) / visits
+    }
+}
+
+/// An action of a decision node, with the chance node of its outcome.
+struct Edge<G: game::GameState> {
+    action: G::Action,
+    reward: Value,
+    outcome: G::Outcome,
+    /// Returns after the reward of the action.
+    returns: Returns,
+    /// States drawn from the outcome, with their decision node.
+    children: Vec<(G, usize)>,
+}
+
+impl<G: game::GameState> Edge<G> {
+    fn value(&self) -> Value {
+        self.reward + self.returns.mean()
+    }
+}
+
+struct Node<G: game::GameState> {
+    state: G,
+    untried: Vec<G::Action>,
+    edges: Vec<Edge<G>>,
+    returns: Returns,
+}
+
+impl<G> Node<G>
+where
+    G: game::GameState,
+    G::Action: game::Discrete,
+{
+    fn new(state: G) -> Self {
+        Self {
+            untried: state.legal_actions().collect(),
+            edges: Vec::new(),
+            returns: Returns::default(),
+            state,
+        }
+    }
+}
+
+impl<Rollout> Mcts<Rollout> {
+    pub fn decide_until<G>(
+        &mut self,
+        state: &G,
+        constraint: SearchConstraint,
+    ) -> Decision<G::Action>
+    where
+        G: game::GameState + Clone + PartialEq,
+        G::Outcome: game::Outcome<G> + Clone,
+        G::Action: game::Discrete + Clone,
+        Value: From<G::Reward>,
+        Rollout: RolloutPolicy<G>,
+    {
+        let mut tree = vec![Node::new(state.clone())];
+
+        let mut iterations = 0;
+        loop {
+            let is_done = match (constraint.deadline, constraint.max_nodes) {
+                (None, None) => iterations >= self.iterations,
+                (deadline, max_nodes) => {
+                    let is_past_deadline =
+                        deadline.is_some_and(|deadline| Instant::now() >= deadline);
+                    let is_over_budget =
+                        max_nodes.is_some_and(|max_nodes| tree.len() as u64 >= max_nodes);
+                    iterations > 0 && (is_past_deadline || is_over_budget)
+                }
+            };
+
+            if is_done {
+                break;
+            }
+
+            self.iterate(&mut tree, constraint.max_depth);
+            iterations += 1;
+        }
+
+        log::trace!("Searched {iterations} iterations, {} nodes", tree.len());
+
+        let nodes = tree.len() as u64;
+        let root = tree.swap_remove(0);
+        let Some(edge) = root
+            .edges
+            .into_iter()
+            .max_by_key(|edge| edge.returns.visits)
+        else {
+            return Decision::Resign;
+        };
+
+        Decision::Act(EvaluatedAction {
+            eval: Evaluation {
+                value: edge.value(),
+                // The rollouts have no fixed depth.
+                min_depth: MaxDepth::new(0),
+                truncated: false,
+                cutoff: 0.0,
+                sampled: true,
+                samples: 1,
+                variance: edge.returns.variance(),
+            },
+            action: edge.action,
+            nodes,
+        })
+    }
+
+    /// Walks down the tree to a leaf, expands it and backs up the return of a rollout from it.
+    fn iterate<G>(&mut self, tree: &mut Vec<Node<G>>, max_depth: MaxDepth)
+    where
+        G: game::GameState + Clone + PartialEq,
+        G::Outcome: game::Outcome<G> + Clone,
+        G::Action: game::Discrete + Clone,
+        Value: From<G::Reward>,
+        Rollout: RolloutPolicy<G>,
+    {
+        let mut path = Vec::new();
+        let mut node_id = 0;
+
+        let leaf_return = loop {
+            let node = &mut tree[node_id];
+            // The root is always expanded, so there is an action to pick.
+            if !path.is_empty() && path.len() >= usize::from(max_depth.max_u8()) {
+                break self.rollout(node.state.clone());
+            }
+
+            let edge_id = match node.untried.pop() {
+                Some(action) => {
+                    let Ok((reward, outcome)) = node.state.clone().outcome(action.clone()) else {
+                        unreachable!("legal actions should be applicable");
+                    };
+
+                    node.edges.push(Edge {
+                        action,
+                        reward: Value::from(reward),
+                        outcome,
+                        returns: Returns::default(),
+                        children: Vec::new(),
+                    });
+                    node.edges.len() - 1
+                }
+                None if node.edges.is_empty() => break 0.0,
+                None => self.select(node),
+            };
+
+            path.push((node_id, edge_id));
+
+            let edge = &tree[node_id].edges[edge_id];
+            let state = edge.outcome.clone().collapse_with(&mut self.rng);
+            let child = edge.children.iter().find(|(child, _)| *child == state);
+            if let Some(&(_, child_id)) = child {
+                node_id = child_id;
+                continue;
+            }
+
+            let child_id = tree.len();
+            tree.push(Node::new(state.clone()));
+            tree[node_id].edges[edge_id]
+                .children
+                .push((state.clone(), child_id));
+
+            node_id = child_id;
+            break self.rollout(state);
+        };
+
+        tree[node_id].returns.add(leaf_return);
+
+        let mut value = leaf_return;
+        for (node_id, edge_id) in path.into_iter().rev() {
+            let edge = &mut tree[node_id].edges[edge_id];
+            edge.returns.add(value);
+            value += edge.reward;
+
+            tree[node_id].returns.add(value);
+        }
+    }
+
+    /// Picks the edge with the highest upper confidence bound.
+    fn select<G: game::GameState>(&self, node: &Node<G>) -> usize {
+        // Scale the exploration to the values of the game, which are not bounded.
+        let scale = node.returns.mean().abs().max(1.0) * self.exploration;
+        let log_visits = (node.returns.visits as Value).ln();
+
+        let bound = |edge: &Edge<G>| {
+            edge.value() + scale * (log_visits / edge.returns.visits as Value).sqrt()
+        };
+
+        (0..node.edges.len())
+            .max_by(|&a, &b| bound(&node.edges[a]).total_cmp(&bound(&node.edges[b])))
+            .expect("only nodes with edges are selected from")
+    }
+
+    /// Returns the sum of the rewards of a game played from `state` by the rollout policy.
+    fn rollout<G>(&mut self, mut state: G) -> Value
+    where
+        G: game::GameState,
+        G::Outcome: game::Outcome<G>,
+        Value: From<G::Reward>,
+        Rollout: RolloutPolicy<G>,
+    {
+        let mut total = 0.0;
+
+        for _ in 0..self.rollout_depth {
+            let Some(action) = self.rollout.choose(&state, &mut self.rng) else {
+                break;
+            };
+
+            let Ok((reward, outcome)) = state.outcome(action) else {
+                unreachable!("the rollout policy should only pick legal actions");
+            };
+
+            total += Value::from(reward);
+            state = outcome.collapse_with(&mut self.rng);
+        }
+
+        total
+    }
+}
+
+impl<G, Rollout> super::Bot<G> for Mcts<Rollout>
+where
+    G: game::GameState + Clone + PartialEq,
+    G::Outcome: game::Outcome<G> + Clone,
+    G::Action: game::Discrete + Clone,
+    Value: From<G::Reward>,
+    Rollout: RolloutPolicy<G>,
+{
+    fn decide_until(&mut self, state: &G, constraint: SearchConstraint) -> Decision<G::Action> {
+        Mcts::decide_until(self, state, constraint)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{GreedyRollout, Mcts};
+    use crate::bots::mean_max::max_depth::MaxDepth;
+    use crate::bots::mean_max::searcher::{Decision, SearchConstraint};
+    use crate::game::twenty_forty_eight::{board::Direction, State};
+
+    #[test]
+    fn test_decide() {
+        let mut mcts = Mcts::new(GreedyRollout).with_iterations(500).with_seed(7);
+
+        // Only merging the two big tiles keeps the game going.
+        let state = State::from_cells([[2, 3, 2, 3], [3, 2, 3, 2], [2, 3, 2, 3], [3, 2, 9, 9]]);
+        let Decision::Act(act) = mcts.decide_until(&state, SearchConstraint::new()) else {
+            panic!("the game is not over:\n{state}");
+        };
+        assert!(matches!(act.action, Direction::Left | Direction::Right));
+        assert!(act.eval.value >= 1024.0);
+
+        // Without a tree below the root, the rollouts still pick the action.
+        let constraint = SearchConstraint::new().with_max_depth(MaxDepth::new(0));
+        let Decision::Act(act) = mcts.decide_until(&state, constraint) else {
+            panic!("the game is not over:\n{state}");
+        };
+        assert!(matches!(act.action, Direction::Left | Direction::Right));
+
+        let state = State::from_cells([[1, 2], [2, 1]]);
+        let decision = mcts.decide_until(&state, SearchConstraint::new());
+        assert_eq!(decision, Decision::Resign);
+    }
+}
@@ -0,0 +1,288 @@
+use super::logger::{Logger, LoggerSettings};
+use super::max_depth::MaxDepth;
+use super::searcher::cache::CacheKind;
+use super::{searcher, EvaluationCaches, MeanMax, Parallelism};
+use crate::game;
+use std::collections::HashMap;
+use std::fmt::{Debug, Display};
+use std::hash::Hash;
+use std::marker::PhantomData;
+use std::sync::{mpsc, Arc, Mutex};
+use thiserror::Error;
+
+/// Creates the heuristic of a searcher, in the thread of the searcher.
+pub type HeuristicFactory<H> = Arc<dyn Fn() -> H + Send + Sync>;
+
+/// Bytes the evaluation caches of the searchers can take, see
+/// [`CacheUsage::bytes`](searcher::cache::CacheUsage::bytes), or values they can hold.
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum CacheBudget {
+    /// One cache shared by every searcher, so they reuse each other's evaluations.
+    Shared(usize),
+    /// A cache of its own for each searcher.
+    PerThread(usize),
+    /// A cache of its own for each searcher, holding this many values. Unlike the byte budgets a
+    /// priority cache is only allocated as it fills, so short-lived bots stay cheap.
+    PerThreadValues(usize),
+}
+
+impl CacheBudget {
+    /// Values of the cache of each searcher by default.
+    pub const DEFAULT_VALUES: usize = 0xF0000;
+}
+
+impl Default for CacheBudget {
+    fn default() -> Self {
+        Self::PerThreadValues(Self::DEFAULT_VALUES)
+    }
+}
+
+#[derive(Debug, Error, Clone, Copy, PartialEq, Eq)]
+pub enum BuildError {
+    #[error("the bot needs at least one searcher thread")]
+    NoSearchers,
+    #[error("a cache budget of {0} bytes has no room for evaluations")]
+    CacheBudgetTooSmall(usize),
+    #[error("the initial depth of the search must be bounded")]
+    UnlimitedInitialDepth,
+}
+
+/// Settings of a [`MeanMax`] bot, checked when it's built.
+pub struct MeanMaxBuilder<G, H> {
+    /// Number of searcher threads, one per available core if `None`.
+    threads: Option<usize>,
+    cache_budget: CacheBudget,
+    cache_kind: CacheKind,
+    heuristic: HeuristicFactory<H>,
+    initial_depth: MaxDepth,
+    parallelism: Parallelism,
+    canonical_cache: bool,
+    logger_settings: LoggerSettings,
+    game: PhantomData<fn() -> G>,
+}
+
+impl<G, H> MeanMaxBuilder<G, H> {
+    /// Starts from the settings of [`MeanMax::new`], with the default heuristic.
+    pub fn new() -> Self
+    where
+        H: Default + 'static,
+    {
+        Self {
+            threads: None,
+            cache_budget: CacheBudget::default(),
+            cache_kind: CacheKind::default(),
+            heuristic: Arc::new(H::default),
+            initial_depth: MaxDepth::new(0),
+            parallelism: Parallelism::default(),
+            canonical_cache: false,
+            logger_settings: LoggerSettings::default(),
+            game: PhantomData,
+        }
+    }
+
+    /// Number of searcher threads. Searches with a node budget only use one of them, see
+    /// [`searcher::SearchConstraint::with_max_nodes`].
+    #[must_use]
+    pub fn with_threads(mut self, threads: usize) -> Self {
+        self.threads = Some(threads);
+        self
+    }
+
+    #[must_use]
+    pub fn with_cache_budget(mut self, cache_budget: CacheBudget) -> Self {
+        self.cache_budget = cache_budget;
+        self
+    }
+
+    #[must_use]
+    pub fn with_cache_kind(mut self, cache_kind: CacheKind) -> Self {
+        self.cache_kind = cache_kind;
+        self
+    }
+
+    /// Gives every searcher a copy of `heuristic`.
+    #[must_use]
+    pub fn with_heuristic(self, heuristic: H) -> Self
+    where
+        H: Clone + Send + Sync + 'static,
+    {
+        self.with_heuristic_factory(move || heuristic.clone())
+    }
+
+    /// Gives every searcher the heuristic created by `factory`.
+    #[must_use]
+    pub fn with_heuristic_factory(
+        mut self,
+        factory: impl Fn() -> H + Send + Sync + 'static,
+    ) -> Self {
+        self.heuristic = Arc::new(factory);
+        self
+    }
+
+    /// Depth of the first search past the heuristic alone, when the search goes deeper and
+    /// deeper.
+    #[must_use]
+    pub fn with_initial_depth(mut self, initial_depth: MaxDepth) -> Self {
+        self.initial_depth = initial_depth;
+        self
+    }
+
+    #[must_use]
+    pub fn with_parallelism(mut self, parallelism: Parallelism) -> Self {
+        self.parallelism = parallelism;
+        self
+    }
+
+    /// See [`MeanMax::canonical_cache`].
+    #[must_use]
+    pub fn with_canonical_cache(mut self, canonical_cache: bool) -> Self {
+        self.canonical_cache = canonical_cache;
+        self
+    }
+
+    #[must_use]
+    pub fn with_logger_settings(mut self, logger_settings: LoggerSettings) -> Self {
+        self.logger_settings = logger_settings;
+        self
+    }
+}
+
+impl<G, H: Default + 'static> Default for MeanMaxBuilder<G, H> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl<G, H> MeanMaxBuilder<G, H>
+where
+    G: game::GameState + Send + Clone + Display + 'static,
+    G::Outcome:
+        game::DiscreteDistribution<T = G> + game::Canonical + Hash + Ord + Clone + Display + Send,
+    G::Action: game::Discrete + Send + Clone + Display + PartialEq,
+    searcher::Value: From<G::Reward> + From<<G::Outcome as game::DiscreteDistribution>::Weight>,
+    H: crate::bots::heuristic::Heuristic<G::Outcome, searcher::Value> + 'static,
+    <G::Outcome as game::DiscreteDistribution>::Weight: Debug,
+{
+    /// Spawns the searcher threads of the bot.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if there are no threads, the cache budget is too small for any evaluation,
+    /// or the initial depth is unlimited.
+    pub fn build(self) -> Result<MeanMax<G, H>, BuildError> {
+        let threads = match self.threads {
+            Some(threads) => threads,
+            None => std::thread::available_parallelism()
+                .ok()
+                //.and_then(|threads| std::num::NonZeroUsize::new(threads.get() - 1))
+                .unwrap_or(std::num::NonZeroUsize::MIN)
+                .get(),
+        };
+
+        if threads == 0 {
+            return Err(BuildError::NoSearchers);
+        }
+
+        let kind = self.cache_kind;
+        if let CacheBudget::Shared(bytes) | CacheBudget::PerThread(bytes) = self.cache_budget {
+            let capacity = kind
+                .capacity_for::<G::Outcome, searcher::Evaluation, searcher::SearchPriority>(bytes);
+            if capacity == 0 {
+                return Err(BuildError::CacheBudgetTooSmall(bytes));
+            }
+        }
+
+        let evaluation_cache = match self.cache_budget {
+            CacheBudget::Shared(bytes) => EvaluationCaches::Shared(kind.with_byte_budget(bytes)),
+            CacheBudget::PerThread(bytes) => EvaluationCaches::PerThread(kind, bytes),
+            CacheBudget::PerThreadValues(values) => EvaluationCaches::PerThreadValues(kind, values),
+        };
+
+        if self.initial_depth.is_unlimited() {
+            return Err(BuildError::UnlimitedInitialDepth);
+        }
+
+        let (result_sender, result_receiver) = mpsc::channel();
+
+        let mut bot = MeanMax {
+            logger: Arc::new(Mutex::new(Logger::with_settings(self.logger_settings))),
+            canonical_cache: self.canonical_cache,
+            parallelism: self.parallelism,
+            cancellation: searcher::CancellationToken::new(),
+            initial_depth: self.initial_depth,
+            heuristic: self.heuristic,
+
+            evaluation_cache,
+            searcher_threads: Vec::new(),
+            sent_tasks: HashMap::new(),
+            result_receiver,
+            result_sender,
+        };
+
+        (0..threads).for_each(|_| bot.add_searcher());
+        Ok(bot)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{BuildError, CacheBudget, MeanMaxBuilder};
+    use crate::bots::heuristic::TwentyFortyEightHeuristic;
+    use crate::bots::mean_max::max_depth::MaxDepth;
+    use crate::bots::mean_max::searcher::{Decision, SearchConstraint};
+    use crate::game::twenty_forty_eight::State;
+    use std::time::{Duration, Instant};
+
+    type Builder = MeanMaxBuilder<State<4, 4>, TwentyFortyEightHeuristic<4, 4>>;
+
+    #[test]
+    fn test_build() {
+        let error = |builder: Builder| builder.build().err();
+        assert_eq!(
+            error(Builder::new().with_threads(0)),
+            Some(BuildError::NoSearchers)
+        );
+        assert_eq!(
+            error(Builder::new().with_cache_budget(CacheBudget::PerThread(1000))),
+            Some(BuildError::CacheBudgetTooSmall(1000))
+        );
+        assert_eq!(
+            error(Builder::new().with_initial_depth(MaxDepth::Unlimited)),
+            Some(BuildError::UnlimitedInitialDepth)
+        );
+
+        // The default caches of MeanMax::new are only allocated as they fill.
+        let ai = Builder::new().with_threads(2).build().unwrap();
+        let usage = ai.cache_usage();
+        assert_eq!(usage.capacity, 2 * CacheBudget::DEFAULT_VALUES);
+        assert!(usage.bytes < 1 << 20, "{usage}");
+
+        let mut ai = Builder::new()
+            .with_threads(2)
+            .with_cache_budget(CacheBudget::PerThread(1 << 20))
+            .with_heuristic(TwentyFortyEightHeuristic::new())
+            .with_initial_depth(MaxDepth::new(2))
+            .build()
+            .unwrap();
+        assert_eq!(ai.searcher_threads.len(), 2);
+
+        let state = State::from_cells([[1, 0, 0, 0], [2, 0, 0, 0], [0, 1, 0, 0], [0, 0, 0, 0]]);
+        let deadline = Instant::now() + Duration::from_millis(20);
+        let analysis = ai.analyze(&state, SearchConstraint::new().with_deadline(deadline));
+        assert!(matches!(analysis.decision, Decision::Act(_)));
+
+        let usage = ai.cache_usage();
+        assert!(usage.entries > 0);
+        assert!(usage.bytes <= 2 << 20, "{usage}");
+
+        // The heuristic alone, then straight to the initial depth
+        let mut depths: Vec<_> = analysis
+            .stats
+            .iterations
+            .iter()
+            .map(|it| it.depth)
+            .collect();
+        depths.sort();
+        assert_eq!(depths[..2], [MaxDepth::new(0), MaxDepth::new(2)]);
+    }
+}
@@ -0,0 +1,221 @@
+//! Iterative deepening, shared by the [`Parallelism`](super::Parallelism) modes.
+//!
+//! The modes only decide how the searchers share an iteration. [`Deepening`] decides which depths
+//! are searched, when the search ends and which decision it gives.
+
+use super::logger::{Logger, Prediction, SearchHandle};
+use super::max_depth::MaxDepth;
+use super::searcher::stats::SearchStats;
+use super::searcher::{
+    CancellationToken, Decision, EvaluatedAction, SearchConstraint, SearchError,
+};
+use super::ThreadedSearch;
+use std::collections::HashMap;
+use std::fmt::Display;
+use std::sync::Mutex;
+use std::time::{Duration, Instant};
+
+pub(super) struct Deepening<'a, A> {
+    constraint: SearchConstraint,
+    /// Search deeper and deeper if the search may stop early, even if only by being cancelled,
+    /// so there is a shallower decision to fall back on.
+    is_iterative: bool,
+    /// Depth searched after the initial search of the heuristic alone.
+    initial_depth: MaxDepth,
+    /// Next depth to hand out, `None` once the maximum depth was handed out.
+    next_depth: Option<MaxDepth>,
+    /// Stop the searchers early enough for the search to end on time.
+    searcher_deadline: Option<Instant>,
+    cancellation: CancellationToken,
+    logger: &'a Mutex<Logger>,
+    search_handle: &'a SearchHandle,
+
+    decision: Option<Decision<A>>,
+    evaluated_actions: Vec<EvaluatedAction<A>>,
+    /// Decision of a stopped search, only used if no search was completed.
+    partial: Option<(Decision<A>, Vec<EvaluatedAction<A>>)>,
+    pub stats: SearchStats,
+    /// Durations of the completed iterations, sorted by depth.
+    iterations: Vec<(MaxDepth, Duration)>,
+    /// Predicted durations of the iterations in progress.
+    predictions: HashMap<MaxDepth, Prediction>,
+    timed_out: bool,
+    done: bool,
+}
+
+impl<'a, A: Clone + Display> Deepening<'a, A> {
+    pub fn new(
+        constraint: SearchConstraint,
+        initial_depth: MaxDepth,
+        cancellation: CancellationToken,
+        logger: &'a Mutex<Logger>,
+        search_handle: &'a SearchHandle,
+    ) -> Self {
+        let is_iterative = constraint.deadline.is_some()
+            || constraint.max_nodes.is_some()
+            || constraint.max_depth.is_unlimited();
+
+        let (first_depth, initial_depth) = match is_iterative {
+            // Start with the heuristic alone, which always gives a decision, then go deeper from
+            // the initial depth
+            true => (MaxDepth::new(0), initial_depth.min(constraint.max_depth)),
+            // Otherwise, search with the maximum depth
+            false => (constraint.max_depth, constraint.max_depth),
+        };
+
+        let margin = logger.lock().unwrap().deadline_miss_model.margin();
+        let searcher_deadline = constraint
+            .deadline
+            .map(|deadline| deadline.checked_sub(margin).unwrap_or(deadline));
+
+        Self {
+            constraint,
+            is_iterative,
+            initial_depth,
+            next_depth: Some(first_depth),
+            searcher_deadline,
+            cancellation,
+            logger,
+            search_handle,
+            decision: None,
+            evaluated_actions: Vec::new(),
+            partial: None,
+            stats: SearchStats::default(),
+            iterations: Vec::new(),
+            predictions: HashMap::new(),
+            timed_out: false,
+            done: false,
+        }
+    }
+
+    pub fn searcher_deadline(&self) -> Option<Instant> {
+        self.searcher_deadline
+    }
+
+    /// A node budget is shared by the whole search and makes it reproducible, so its work is
+    /// done one search at a time, filling the caches in the same order with any number of
+    /// threads.
+    pub fn is_sequential(&self) -> bool {
+        self.constraint.max_nodes.is_some()
+    }
+
+    /// Nodes left in the budget of the search.
+    fn remaining_nodes(&self) -> Option<u64> {
+        let max_nodes = self.constraint.max_nodes?;
+        Some(max_nodes.saturating_sub(self.stats.nodes))
+    }
+
+    /// Constraint and cancellation of the iteration that searches `depth`.
+    pub fn iteration(&self, depth: MaxDepth) -> (SearchConstraint, Option<CancellationToken>) {
+        // No deadline, node budget or cancellation for the initial search of the heuristic
+        // alone, so there is always a decision. The deeper searches may be stopped.
+        let is_initial = self.is_iterative && depth == MaxDepth::new(0);
+        let constraint = SearchConstraint {
+            deadline: self.searcher_deadline.filter(|_| !is_initial),
+            max_nodes: self.remaining_nodes().filter(|_| !is_initial),
+            max_depth: depth,
+            ..self.constraint
+        };
+
+        (constraint, (!is_initial).then(|| self.cancellation.clone()))
+    }
+
+    /// Hands out the next depth to search, `None` once the search is done.
+    pub fn next_depth(&mut self) -> Option<MaxDepth> {
+        let depth = self.next_depth.filter(|_| !self.done)?;
+        if self.remaining_nodes() == Some(0) {
+            self.done = true;
+            return None;
+        }
+
+        // Don't start a depth that is unlikely to finish before the searchers are stopped.
+        let mut logger = self.logger.lock().unwrap();
+        let model = &mut logger.deadline_miss_model;
+        let prediction = model.predict(&self.iterations, depth);
+        if let (Some(deadline), Some(prediction)) = (self.searcher_deadline, prediction) {
+            if Instant::now() + prediction.duration > deadline {
+                let predicted = prediction.duration;
+                log::trace!("Skipping depth {depth}, predicted to take {predicted:?}");
+                model.skipped_iterations += 1;
+                self.done = true;
+                return None;
+            }
+
+            self.predictions.insert(depth, prediction);
+        }
+
+        self.next_depth =
+            (depth < self.constraint.max_depth).then(|| (depth + 1).max(self.initial_depth));
+        Some(depth)
+    }
+
+    /// Keeps the decision of an iteration that searched `depth` to the end, if it's the deepest.
+    pub fn complete(
+        &mut self,
+        depth: MaxDepth,
+        duration: Duration,
+        decision: Decision<A>,
+        evaluated_actions: Vec<EvaluatedAction<A>>,
+    ) {
+        let mut logger = self.logger.lock().unwrap();
+        logger.register_search_result(self.search_handle, &decision);
+        if let Some(prediction) = self.predictions.remove(&depth) {
+            logger
+                .deadline_miss_model
+                .record_prediction(prediction, duration);
+        }
+        drop(logger);
+
+        let index = self.iterations.partition_point(|&(other, _)| other < depth);
+        self.iterations.insert(index, (depth, duration));
+
+        // Move the depth limit higher for a deeper search
+        let min_depth = decision.eval().min_depth;
+        self.next_depth = self.next_depth.map(|next| next.max(min_depth + 1));
+
+        let is_deeper = self
+            .decision
+            .as_ref()
+            .is_none_or(|best| min_depth > best.eval().min_depth);
+        if is_deeper {
+            self.decision = Some(decision);
+            self.evaluated_actions = evaluated_actions;
+        }
+
+        let best = self.decision.as_ref().expect("a decision was just kept");
+        if matches!(best, Decision::Resign) || best.eval().min_depth >= self.constraint.max_depth {
+            self.done = true;
+        }
+    }
+
+    /// Ends the search after an iteration was stopped.
+    pub fn stop(
+        &mut self,
+        err: SearchError,
+        partial: Decision<A>,
+        evaluated_actions: Vec<EvaluatedAction<A>>,
+    ) {
+        self.timed_out |= matches!(err, SearchError::TimeOut);
+        self.done = true;
+
+        // Keep the first partial decision, in case no search is completed.
+        if self.decision.is_none() && self.partial.is_none() {
+            self.partial = Some((partial, evaluated_actions));
+        }
+    }
+
+    pub fn finish(self) -> ThreadedSearch<A> {
+        let (decision, evaluated_actions) = match self.decision {
+            Some(decision) => (decision, self.evaluated_actions),
+            None => self.partial.expect("every search should give a decision"),
+        };
+
+        ThreadedSearch {
+            decision,
+            evaluated_actions,
+            stats: self.stats,
+            iterations: self.iterations,
+            timed_out: self.timed_out,
+        }
+    }
+}
@@ -8,7 +8,7 @@ use crate::utils;
 use std::{
     fmt::Display,
     sync::{Arc, Mutex, MutexGuard},
-    time::Instant,
+    time::{Duration, Instant},
 };
 
 pub(super) struct SearchHandle(usize);
@@ -19,10 +19,130 @@ pub struct SearchInfo {
     pub end_time: Option<Instant>,
 }
 
+/// Learns how long the iterations of a search take, to only start the ones that can finish
+/// before the deadline.
+#[derive(Clone, Debug, Default)]
+pub struct DeadlineModel {
+    /// How late the searches end after their deadline, in seconds.
+    pub misses: WeightedAverage<f64, f64>,
+    /// How late the searches end after the deadline given to the searchers, in seconds.
+    pub overshoots: WeightedAverage<f64, f64>,
+    /// Logarithm of the ratio between the durations of consecutive depths.
+    pub log_branching_factor: WeightedAverage<f64, f64>,
+    /// Logarithm of the ratio between the actual and predicted durations of iterations.
+    pub log_prediction_errors: WeightedAverage<f64, f64>,
+    /// Iterations that were not started since they were predicted to miss the deadline.
+    pub skipped_iterations: u64,
+}
+
+/// Predicted duration of an iteration.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Prediction {
+    /// Duration extrapolated from the previous iteration with the branching factor.
+    pub extrapolated: Duration,
+    /// Extrapolated duration corrected by the errors of the previous predictions.
+    pub duration: Duration,
+}
+
+impl DeadlineModel {
+    /// Iterations shorter than this are dominated by noise, so they are not learned from.
+    const MIN_DURATION: Duration = Duration::from_micros(200);
+    /// Weight kept by the previous samples when a sample is learned, so the model follows the
+    /// game as the board changes.
+    const DECAY: f64 = 0.9;
+
+    /// Learns from the durations of the completed iterations of a search, sorted by depth.
+    pub fn record_iterations(&mut self, iterations: &[(MaxDepth, Duration)]) {
+        for pair in iterations.windows(2) {
+            let [(depth, duration), (next_depth, next_duration)] = *pair else {
+                unreachable!("windows have 2 elements");
+            };
+
+            if depth + 1 != next_depth || duration < Self::MIN_DURATION {
+                continue;
+            }
+
+            let ratio = next_duration.as_secs_f64() / duration.as_secs_f64();
+            self.log_branching_factor.decay(Self::DECAY);
+            self.log_branching_factor += Weighted::<f64, f64>::new(ratio.ln());
+        }
+    }
+
+    /// Effective branching factor learned from the recent searches.
+    pub fn branching_factor(&self) -> Option<f64> {
+        let has_samples = *self.log_branching_factor.weight() > 0.0;
+        has_samples.then(|| self.log_branching_factor.clone().evaluate().exp())
+    }
+
+    /// How many times longer the iterations take than extrapolated, learned from the recent
+    /// predictions.
+    pub fn prediction_error(&self) -> f64 {
+        match *self.log_prediction_errors.weight() > 0.0 {
+            true => self.log_prediction_errors.clone().evaluate().exp(),
+            false => 1.0,
+        }
+    }
+
+    /// Predicts how long searching `depth` takes from the completed iterations of the current
+    /// search, sorted by depth.
+    pub fn predict(
+        &self,
+        iterations: &[(MaxDepth, Duration)],
+        depth: MaxDepth,
+    ) -> Option<Prediction> {
+        let &(last_depth, last_duration) = iterations.last()?;
+        if depth <= last_depth || depth.is_unlimited() || last_duration < Self::MIN_DURATION {
+            return None;
+        }
+
+        let branching_factor = self.branching_factor()?;
+        let levels = depth.max_u8() - last_depth.max_u8();
+        let seconds = last_duration.as_secs_f64() * branching_factor.powi(levels.into());
+
+        Some(Prediction {
+            extrapolated: Duration::try_from_secs_f64(seconds).ok()?,
+            duration: Duration::try_from_secs_f64(seconds * self.prediction_error()).ok()?,
+        })
+    }
+
+    /// Learns how far the extrapolation of `prediction` was from the `actual` duration.
+    pub fn record_prediction(&mut self, prediction: Prediction, actual: Duration) {
+        let error = actual.as_secs_f64() / prediction.extrapolated.as_secs_f64();
+        self.log_prediction_errors.decay(Self::DECAY);
+        self.log_prediction_errors += Weighted::<f64, f64>::new(error.ln());
+    }
+
+    /// Learns how late a search ended after the deadline given to its searchers.
+    pub fn record_overshoot(&mut self, searcher_deadline: Instant, end_time: Instant) {
+        let overshoot = end_time.saturating_duration_since(searcher_deadline);
+        self.overshoots.decay(Self::DECAY);
+        self.overshoots += Weighted::<f64, f64>::new(overshoot.as_secs_f64());
+    }
+
+    /// How much earlier the searchers should stop to end the search on time.
+    pub fn margin(&self) -> Duration {
+        if *self.overshoots.weight() == 0.0 {
+            return Duration::ZERO;
+        }
+
+        Duration::try_from_secs_f64(self.overshoots.clone().evaluate()).unwrap_or(Duration::ZERO)
+    }
+}
+
+/// What the [`Logger`] prints, all off by default.
+#[derive(Copy, Clone, Debug, Default, PartialEq, Eq)]
+pub struct LoggerSettings {
+    pub log_search_results: bool,
+    pub print_size_of_critical_structs: bool,
+    pub clear_screen: bool,
+    pub log_deadline_miss: bool,
+    pub print_cache_info: bool,
+}
+
 pub struct Logger {
     pub global_cache_hit_chance_model: Accumulator<MaxDepth, WeightedAverage<f64, f64>>,
     pub cache_hit_depth_model: Accumulator<MaxDepth, WeightedAverage<f64, f64>>,
-    pub deadline_miss_model: WeightedAverage<f64, f64>,
+    pub deadline_miss_model: DeadlineModel,
     pub search_log: Vec<SearchInfo>,
 
     pub log_search_results: bool,
@@ -33,18 +153,18 @@ pub struct Logger {
 }
 
 impl Logger {
-    pub(super) fn new() -> Self {
+    pub(super) fn with_settings(settings: LoggerSettings) -> Self {
         Logger {
             global_cache_hit_chance_model: Accumulator::new(),
             cache_hit_depth_model: Accumulator::new(),
-            deadline_miss_model: WeightedAverage::default(),
+            deadline_miss_model: DeadlineModel::default(),
             search_log: Vec::new(),
 
-            print_size_of_critical_structs: false,
-            log_search_results: false,
-            clear_screen: false,
-            log_deadline_miss: false,
-            print_cache_info: false,
+            print_size_of_critical_structs: settings.print_size_of_critical_structs,
+            log_search_results: settings.log_search_results,
+            clear_screen: settings.clear_screen,
+            log_deadline_miss: settings.log_deadline_miss,
+            print_cache_info: settings.print_cache_info,
         }
     }
 
@@ -140,10 +260,6 @@ impl Logger {
 
         search_info.end_time = Some(end_time);
 
-        if !self.log_deadline_miss {
-            return;
-        }
-
         let deadline = search_info.constraint.deadline;
         let Some(deadline) = deadline else { return };
 
@@ -161,7 +277,7 @@ impl Logger {
         // BUG: This can be thrown off if a high miss happens at the start.
 
         // if miss_err.is_nan() || Duration::from_secs_f64(miss_err) <= outlier_threshold {
-        self.deadline_miss_model += Weighted::<f64, f64>::new(miss_seconds);
+        self.deadline_miss_model.misses += Weighted::<f64, f64>::new(miss_seconds);
         // } else {
         //     eprintln!(
         //         "Ignoring miss since it has a high error ({miss_duration:.1?}>{outlier_threshold:.1?})",
@@ -169,13 +285,29 @@ impl Logger {
         //     );
         // }
 
+        if !self.log_deadline_miss {
+            return;
+        }
+
         // TODO: We should probably be using chrono
         let miss_duration = utils::get_signed_duration(miss_seconds);
         println!("Deadline missed by {miss_duration:?}");
 
-        let avg_miss_seconds = self.deadline_miss_model.clone().evaluate();
+        let model = &self.deadline_miss_model;
+        let avg_miss_seconds = model.misses.clone().evaluate();
         let avg_miss = utils::get_signed_duration(avg_miss_seconds);
         println!("Avg miss: {avg_miss:?}");
+
+        if let Some(branching_factor) = model.branching_factor() {
+            println!("Effective branching factor: {branching_factor:.1}");
+        }
+
+        if *model.log_prediction_errors.weight() > 0.0 {
+            let error = model.prediction_error();
+            println!("Iteration time over prediction: {error:.2}x");
+        }
+
+        println!("Skipped iterations: {}", model.skipped_iterations);
     }
 }
 
@@ -236,3 +368,61 @@ impl LoggerHandle {
         }
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::DeadlineModel;
+    use crate::bots::mean_max::max_depth::MaxDepth;
+    use std::time::{Duration, Instant};
+
+    #[test]
+    fn test_deadline_model() {
+        let mut model = DeadlineModel::default();
+        let iterations = [1, 4, 16].map(Duration::from_millis);
+        let iterations: Vec<_> = (1..).map(MaxDepth::new).zip(iterations).collect();
+
+        assert_eq!(model.predict(&iterations, MaxDepth::new(4)), None);
+        model.record_iterations(&iterations);
+
+        let branching_factor = model.branching_factor().unwrap();
+        assert!((branching_factor - 4.0).abs() < 1e-6);
+
+        let prediction = model.predict(&iterations, MaxDepth::new(5)).unwrap();
+        assert!(
+            prediction.duration.abs_diff(Duration::from_millis(256)) < Duration::from_micros(1)
+        );
+        assert_eq!(model.predict(&iterations, MaxDepth::new(3)), None);
+
+        // Iterations that take twice as long as extrapolated are predicted so.
+        model.record_prediction(prediction, 2 * prediction.extrapolated);
+        let prediction = model.predict(&iterations, MaxDepth::new(4)).unwrap();
+        assert_eq!(prediction.extrapolated, Duration::from_millis(64));
+        assert!(
+            prediction.duration.abs_diff(Duration::from_millis(128)) < Duration::from_micros(1)
+        );
+
+        // The recent searches count more than the older ones.
+        let slower = [1, 8, 64].map(Duration::from_millis);
+        let slower: Vec<_> = (1..).map(MaxDepth::new).zip(slower).collect();
+        for _ in 0..20 {
+            model.record_iterations(&slower);
+        }
+        assert!((model.branching_factor().unwrap() - 8.0).abs() < 0.1);
+
+        assert_eq!(model.margin(), Duration::ZERO);
+        let deadline = Instant::now();
+        model.record_overshoot(deadline, deadline + Duration::from_millis(19));
+        model.record_overshoot(deadline, deadline);
+        assert!(model.margin().abs_diff(Duration::from_millis(9)) < Duration::from_micros(1));
+
+        // The margin shrinks again once the searches end on time.
+        for _ in 0..20 {
+            model.record_overshoot(deadline, deadline);
+        }
+        assert!(
+            model.margin() < Duration::from_millis(1),
+            "{:?}",
+            model.margin()
+        );
+    }
+}
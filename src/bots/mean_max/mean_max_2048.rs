@@ -1,29 +1,17 @@
 use crate::bots::heuristic::TwentyFortyEightHeuristic;
-use crate::game::twenty_forty_eight::State;
-use std::sync::{Arc, Mutex};
+use crate::game::twenty_forty_eight::{bitboard, State};
 
 impl<const ROWS: usize, const COLS: usize>
     super::MeanMax<State<COLS, ROWS>, TwentyFortyEightHeuristic<COLS, ROWS>>
 {
     pub fn new() -> Self {
-        let (result_sender, result_receiver) = std::sync::mpsc::channel();
-
-        let mut this = Self {
-            logger: Arc::new(Mutex::new(super::logger::Logger::new())),
-            heuristic: std::marker::PhantomData,
-
-            searcher_threads: Vec::new(),
-            result_receiver,
-            result_sender,
-        };
-
-        let num_threads = std::thread::available_parallelism()
-            .ok()
-            //.and_then(|threads| std::num::NonZeroUsize::new(threads.get() - 1))
-            .unwrap_or(std::num::NonZeroUsize::MIN);
+        Self::with_default_searchers()
+    }
 
-        (0..num_threads.get()).for_each(|_| this.add_searcher());
-        this
+    pub fn builder(
+    ) -> super::builder::MeanMaxBuilder<State<COLS, ROWS>, TwentyFortyEightHeuristic<COLS, ROWS>>
+    {
+        super::builder::MeanMaxBuilder::new()
     }
 }
 
@@ -34,3 +22,10 @@ impl<const ROWS: usize, const COLS: usize> Default
         Self::new()
     }
 }
+
+// NOTE: There is no inherent `new` here so `MeanMax::new()` can still infer the board size.
+impl Default for super::MeanMax<bitboard::State, TwentyFortyEightHeuristic<4, 4>> {
+    fn default() -> Self {
+        Self::with_default_searchers()
+    }
+}
@@ -1,44 +1,126 @@
+pub mod builder;
+mod deepening;
 pub mod logger;
 pub mod max_depth;
 pub mod mean_max_2048;
 pub mod searcher;
+mod split;
 
 use crate::game;
-use std::collections::HashSet;
+use std::collections::{HashMap, HashSet};
 use std::fmt::{Debug, Display};
 use std::hash::Hash;
-use std::marker::PhantomData;
+use std::panic::{self, AssertUnwindSafe};
 use std::sync::{mpsc, Arc, Mutex};
 use std::thread::JoinHandle;
+use std::time::{Duration, Instant};
 
+#[derive(Clone)]
 struct Task<Game> {
     task_id: usize,
     state: Game,
     search_constraint: searcher::SearchConstraint,
+    canonical_cache: bool,
+    cancellation: Option<searcher::CancellationToken>,
 }
 
 struct SearchResult<Game: game::GameState> {
     task_id: usize,
-    state: Game,
     result: searcher::DecisionResult<Game::Action>,
+    /// Best of the root actions that were fully evaluated, even if the search timed out.
+    partial: searcher::Decision<Game::Action>,
+    /// Root actions that were fully evaluated, see [`searcher::RootAnalysis`].
+    evaluated_actions: Vec<searcher::EvaluatedAction<Game::Action>>,
+    stats: searcher::stats::SearchStats,
+}
+
+impl<Game: game::GameState> SearchResult<Game> {
+    /// Result of a task whose searcher panicked.
+    fn panicked(task_id: usize) -> Self {
+        Self {
+            task_id,
+            result: Err(searcher::SearchError::Panicked),
+            partial: searcher::Decision::Resign,
+            evaluated_actions: Vec::new(),
+            stats: searcher::stats::SearchStats::default(),
+        }
+    }
+}
+
+/// Result of a search spread over the searcher threads.
+struct ThreadedSearch<A> {
+    decision: searcher::Decision<A>,
+    evaluated_actions: Vec<searcher::EvaluatedAction<A>>,
+    stats: searcher::stats::SearchStats,
+    /// Durations of the completed iterations, sorted by depth.
+    iterations: Vec<(max_depth::MaxDepth, Duration)>,
+    /// Some search was stopped by the deadline.
+    timed_out: bool,
+}
+
+/// How the searcher threads share a search.
+#[derive(Copy, Clone, Debug, Default, PartialEq, Eq)]
+pub enum Parallelism {
+    /// Every thread searches the whole tree, each one level deeper than the previous one.
+    #[default]
+    StaggeredDepths,
+    /// The threads search the same depth together, each iteration is split into the states that
+    /// follow the root actions.
+    SplitWork,
 }
 
 pub struct SearcherThread<Game: game::GameState> {
     thread: JoinHandle<()>,
     task_sender: mpsc::Sender<Task<Game>>,
+    evaluation_cache: Arc<searcher::EvaluationCache<Game>>,
+}
+
+impl<Game: game::GameState> SearcherThread<Game> {
+    /// Closes the task channel, so the thread ends after its current task, and waits for it.
+    fn join(self) {
+        let Self {
+            thread,
+            task_sender,
+            ..
+        } = self;
+
+        drop(task_sender);
+        if thread.join().is_err() {
+            log::error!("A searcher thread panicked");
+        }
+    }
 }
 
-// TODO: Add concurrency to cache and search
 pub struct MeanMax<Game: game::GameState, Heuristic> {
     pub logger: Arc<Mutex<logger::Logger>>,
-    heuristic: PhantomData<Heuristic>,
-
-    //evaluation_cache: lru::LruCache<Game::Outcome, Evaluation>,
+    /// Share cached evaluations between symmetric positions.
+    pub canonical_cache: bool,
+    pub parallelism: Parallelism,
+    cancellation: searcher::CancellationToken,
+    /// First depth past the heuristic alone of the searches that go deeper and deeper.
+    pub initial_depth: max_depth::MaxDepth,
+    /// Creates the heuristic of every new searcher.
+    heuristic: builder::HeuristicFactory<Heuristic>,
+
+    evaluation_cache: EvaluationCaches<Game>,
     pub searcher_threads: Vec<SearcherThread<Game>>,
+    /// Tasks sent to the searchers and not answered yet, to give them again to the replacement
+    /// of a searcher that panicked. Also whether they were given again already.
+    sent_tasks: HashMap<usize, (Task<Game>, bool)>,
     result_receiver: mpsc::Receiver<SearchResult<Game>>,
     result_sender: mpsc::Sender<SearchResult<Game>>,
 }
 
+/// Evaluation caches of the searcher threads.
+enum EvaluationCaches<Game: game::GameState> {
+    /// One cache shared by every searcher.
+    Shared(Arc<searcher::EvaluationCache<Game>>),
+    /// A cache of the kind with a budget of the given bytes for each searcher.
+    PerThread(searcher::cache::CacheKind, usize),
+    /// A cache of the kind holding the given number of values for each searcher.
+    PerThreadValues(searcher::cache::CacheKind, usize),
+}
+
 #[derive(Copy, Clone, Debug, PartialEq, Eq)]
 struct Transition<G: game::GameState> {
     pub action: G::Action,
@@ -48,159 +130,588 @@ struct Transition<G: game::GameState> {
 
 impl<G, H> MeanMax<G, H>
 where
-    H: Default,
     G: game::GameState + Send + Clone + Display + 'static,
-    G::Outcome: game::DiscreteDistribution<T = G> + Hash + Ord + Clone + Display,
-    G::Action: game::Discrete + Send + Clone + Display,
+    G::Outcome:
+        game::DiscreteDistribution<T = G> + game::Canonical + Hash + Ord + Clone + Display + Send,
+    G::Action: game::Discrete + Send + Clone + Display + PartialEq,
     searcher::Value: From<G::Reward> + From<<G::Outcome as game::DiscreteDistribution>::Weight>,
-    H: super::heuristic::Heuristic<G::Outcome, searcher::Value>,
+    H: super::heuristic::Heuristic<G::Outcome, searcher::Value> + 'static,
     <G::Outcome as game::DiscreteDistribution>::Weight: Debug,
 {
-    const DEFAULT_CACHE_SIZE: usize = 0xF0000;
+    /// Creates a bot with one searcher per available core.
+    fn with_default_searchers() -> Self
+    where
+        H: Default,
+    {
+        builder::MeanMaxBuilder::new()
+            .build()
+            .expect("the default settings should be valid")
+    }
 
+    /// Returns a token that stops the [`decide_until`](Self::decide_until) in progress, which
+    /// then returns the best decision found so far.
+    pub fn cancellation_token(&self) -> searcher::CancellationToken {
+        self.cancellation.clone()
+    }
+
+    /// Entries and memory of the evaluation caches, over every searcher.
+    pub fn cache_usage(&self) -> searcher::cache::CacheUsage {
+        match &self.evaluation_cache {
+            EvaluationCaches::Shared(cache) => cache.usage(),
+            EvaluationCaches::PerThread(..) | EvaluationCaches::PerThreadValues(..) => self
+                .searcher_threads
+                .iter()
+                .map(|searcher| searcher.evaluation_cache.usage())
+                .sum(),
+        }
+    }
+
+    /// Returns the decision with the statistics of the search, see [`analyze`](Self::analyze)
+    /// for the evaluations of every root action.
     pub fn decide_until(
         &mut self,
         state: &G,
         constraint: searcher::SearchConstraint,
-    ) -> searcher::Decision<G::Action> {
-        let search_handle = self.logger.lock().unwrap().start_search(state, constraint);
-
-        let mut search_constraint = searcher::SearchConstraint {
-            // No deadline for the initial search
-            deadline: None,
-            // Initial search depth
-            max_depth: match constraint.deadline {
-                // If there is a deadline, start at depth 0 and go deeper
-                Some(_) => max_depth::MaxDepth::new(0),
-                // Otherwise, search with the maximum depth
-                None => constraint.max_depth,
-            },
-        };
+    ) -> (searcher::Decision<G::Action>, searcher::stats::SearchStats) {
+        let analysis = self.analyze(state, constraint);
+        (analysis.decision, analysis.stats)
+    }
 
-        let mut busy_tasks = HashSet::new();
+    /// Searches like [`decide_until`](Self::decide_until), and evaluates every root action.
+    ///
+    /// # Panics
+    ///
+    /// Panics if the bot has no searcher, after a [`shutdown`](Self::shutdown).
+    pub fn analyze(
+        &mut self,
+        state: &G,
+        constraint: searcher::SearchConstraint,
+    ) -> searcher::RootAnalysis<G::Action> {
+        assert!(
+            !self.searcher_threads.is_empty(),
+            "the bot should have a searcher, see `add_searcher`"
+        );
+
+        self.cancellation.reset();
+        let logger = Arc::clone(&self.logger);
+        let search_handle = logger.lock().unwrap().start_search(state, constraint);
+
+        let mut deepening = deepening::Deepening::new(
+            constraint,
+            self.initial_depth,
+            self.cancellation.clone(),
+            &logger,
+            &search_handle,
+        );
+        match self.parallelism {
+            Parallelism::StaggeredDepths => self.search_staggered(state, &mut deepening),
+            Parallelism::SplitWork => self.search_split(state, &mut deepening),
+        }
+        let searcher_deadline = deepening.searcher_deadline();
+        let search = deepening.finish();
+
+        let mut logger = logger.lock().unwrap();
+        logger
+            .deadline_miss_model
+            .record_iterations(&search.iterations);
+        if let (Some(searcher_deadline), true) = (searcher_deadline, search.timed_out) {
+            let model = &mut logger.deadline_miss_model;
+            model.record_overshoot(searcher_deadline, Instant::now());
+        }
+        if logger.print_cache_info {
+            println!("Evaluation cache: {}", self.cache_usage());
+        }
+        logger.end_search(search_handle);
+        drop(logger);
 
-        for (task_id, searcher) in self.searcher_threads.iter().enumerate() {
-            let task = Task {
-                task_id,
-                search_constraint,
-                state: state.clone(),
-            };
+        let decision = search.decision.with_nodes(search.stats.nodes);
+        searcher::RootAnalysis::new(state, decision, search.evaluated_actions, search.stats)
+    }
 
-            search_constraint.deadline = constraint.deadline;
+    /// Gives every searcher the whole search, each one a level deeper than the previous one.
+    fn search_staggered(&mut self, state: &G, deepening: &mut deepening::Deepening<G::Action>) {
+        let searchers = match deepening.is_sequential() {
+            true => 1,
+            false => self.searcher_threads.len(),
+        };
 
-            searcher
-                .task_sender
-                .send(task)
-                .expect("searcher thread should be alive as long as the sender is alive");
+        let mut busy_tasks = HashSet::new();
+        for task_id in 0..searchers {
+            let Some(depth) = deepening.next_depth() else {
+                break;
+            };
 
+            self.send_iteration(task_id, state, deepening, depth);
             busy_tasks.insert(task_id);
-
-            search_constraint.max_depth += 1;
-            search_constraint.max_depth = search_constraint.max_depth.min(constraint.max_depth);
         }
 
-        let mut decision: Option<searcher::Decision<G::Action>> = None;
-        let mut search_done = false;
-
-        // Search deeper loop
         while !busy_tasks.is_empty() {
             let SearchResult {
                 task_id,
-                state: _,
                 result,
-            } = self
-                .result_receiver
-                .recv()
-                .expect("there should be at least one result sender alive");
-            log::trace!("Result from searcher #{task_id}");
+                partial,
+                evaluated_actions,
+                stats,
+            } = self.receive_result();
 
             busy_tasks.remove(&task_id);
+            let iteration = stats.iterations.last().copied();
+            deepening.stats.merge(stats);
 
-            let Ok(new_decision) = result else {
-                search_done = true;
-                continue;
-            };
-
-            let mut logger = self.logger.lock().unwrap();
-            logger.register_search_result(&search_handle, &new_decision);
-
-            search_constraint.max_depth = search_constraint
-                .max_depth
-                .max(new_decision.eval().min_depth);
-
-            match decision {
-                None => decision = Some(new_decision),
-                Some(ref best_decision) => {
-                    if new_decision.eval().min_depth > best_decision.eval().min_depth {
-                        decision = Some(new_decision);
-                    }
-                }
-            }
-
-            // If last decision was Resign break
-            let last_decision = match decision.as_ref().unwrap() {
-                searcher::Decision::Act(last_decision) => last_decision,
-                searcher::Decision::Resign => {
-                    search_done = true;
+            match result {
+                Err(err) => {
+                    deepening.stop(err, partial, evaluated_actions);
                     continue;
                 }
-            };
+                Ok(decision) => {
+                    let iteration = iteration.expect("every search should record its iteration");
+                    deepening.complete(
+                        iteration.depth,
+                        iteration.duration,
+                        decision,
+                        evaluated_actions,
+                    );
+                }
+            }
 
-            // Reached the max_depth, abort
-            if last_decision.eval.min_depth >= constraint.max_depth {
-                search_done = true;
-                continue;
+            if let Some(depth) = deepening.next_depth() {
+                self.send_iteration(task_id, state, deepening, depth);
+                busy_tasks.insert(task_id);
             }
+        }
+    }
 
-            // Move the depth limit higher for a deeper search
-            search_constraint.max_depth += 1;
+    /// Gives the searcher of `task_id` the whole search to `depth`.
+    fn send_iteration(
+        &mut self,
+        task_id: usize,
+        state: &G,
+        deepening: &deepening::Deepening<G::Action>,
+        depth: max_depth::MaxDepth,
+    ) {
+        let (search_constraint, cancellation) = deepening.iteration(depth);
+        log::trace!("Scheduling #{task_id} for {search_constraint}");
+
+        self.send_task(Task {
+            task_id,
+            search_constraint,
+            state: state.clone(),
+            canonical_cache: self.canonical_cache,
+            cancellation,
+        });
+    }
+
+    fn send_task(&mut self, task: Task<G>) {
+        self.searcher_threads[task.task_id]
+            .task_sender
+            .send(task.clone())
+            .expect("searcher thread should be alive as long as the sender is alive");
+        self.sent_tasks.insert(task.task_id, (task, false));
+    }
 
-            if search_done {
-                continue;
+    /// Waits for the next result. The task of a searcher that panicked is given again to its
+    /// replacement, the result is only a panic if the task panics again.
+    fn receive_result(&mut self) -> SearchResult<G> {
+        loop {
+            let search = self
+                .result_receiver
+                .recv()
+                .expect("there should be at least one result sender alive");
+            log::trace!("Result from searcher #{}", search.task_id);
+
+            let (task, was_resent) = self
+                .sent_tasks
+                .remove(&search.task_id)
+                .expect("results should only come from busy searchers");
+            if !matches!(search.result, Err(searcher::SearchError::Panicked)) {
+                return search;
             }
 
-            let task = Task {
-                task_id,
-                search_constraint,
-                state: state.clone(),
-            };
+            self.respawn_searcher(search.task_id);
+            if was_resent {
+                log::error!(
+                    "Searcher #{} panicked again on the same task",
+                    search.task_id
+                );
+                return search;
+            }
 
-            log::trace!("Scheduling #{task_id} for {search_constraint}");
+            let task_id = task.task_id;
+            self.send_task(task);
+            self.sent_tasks
+                .get_mut(&task_id)
+                .expect("the task was just sent")
+                .1 = true;
+        }
+    }
 
-            self.searcher_threads[task_id]
-                .task_sender
-                .send(task)
-                .expect("searcher thread should be alive as long as the sender is alive");
+    pub fn add_searcher(&mut self) {
+        let searcher = self.spawn_searcher();
+        self.searcher_threads.push(searcher);
+    }
 
-            busy_tasks.insert(task_id);
-        }
+    /// Replaces the searcher of `task_id` after it panicked.
+    fn respawn_searcher(&mut self, task_id: usize) {
+        log::error!("Searcher #{task_id} panicked, replacing it");
 
-        self.logger.lock().unwrap().end_search(search_handle);
-        decision.unwrap()
+        let searcher = self.spawn_searcher();
+        std::mem::replace(&mut self.searcher_threads[task_id], searcher).join();
     }
 
-    pub fn add_searcher(&mut self) {
+    fn spawn_searcher(&self) -> SearcherThread<G> {
         let (task_sender, task_reciever) = mpsc::channel::<Task<G>>();
         let result_sender = self.result_sender.clone();
 
         let logger = logger::LoggerHandle::new(self.logger.clone());
+        let evaluation_cache = match &self.evaluation_cache {
+            EvaluationCaches::Shared(cache) => Arc::clone(cache),
+            &EvaluationCaches::PerThread(kind, bytes) => kind.with_byte_budget(bytes),
+            &EvaluationCaches::PerThreadValues(kind, values) => kind.with_capacity(values),
+        };
+        let thread_cache = Arc::clone(&evaluation_cache);
+        let heuristic = Arc::clone(&self.heuristic);
         let thread = std::thread::spawn(move || {
-            let capacity = Self::DEFAULT_CACHE_SIZE.try_into().unwrap();
-            let heuristic = H::default();
-            let mut searcher = searcher::Searcher::new(heuristic, capacity, logger);
+            let mut searcher = panic::catch_unwind(AssertUnwindSafe(|| {
+                searcher::Searcher::new(heuristic(), evaluation_cache, logger)
+            }))
+            .ok();
+
             while let Ok(task) = task_reciever.recv() {
-                let result = searcher.search(task);
+                let task_id = task.task_id;
+                let result = searcher.as_mut().and_then(|searcher| {
+                    panic::catch_unwind(AssertUnwindSafe(|| searcher.search(task))).ok()
+                });
+
+                let Some(result) = result else {
+                    // The searcher may be left broken, the bot replaces the whole thread.
+                    let _ = result_sender.send(SearchResult::panicked(task_id));
+                    break;
+                };
+
                 if result_sender.send(result).is_err() {
                     break;
                 }
             }
         });
 
-        let searcher = SearcherThread {
+        SearcherThread {
             thread,
             task_sender,
+            evaluation_cache: thread_cache,
+        }
+    }
+}
+
+impl<G: game::GameState, H> MeanMax<G, H> {
+    /// Stops the last searcher and waits for its thread to end, returns `false` if there was no
+    /// searcher left.
+    pub fn remove_searcher(&mut self) -> bool {
+        let Some(searcher) = self.searcher_threads.pop() else {
+            return false;
         };
 
-        self.searcher_threads.push(searcher);
+        searcher.join();
+        true
+    }
+
+    /// Stops every searcher and waits for their threads to end.
+    ///
+    /// The bot can't search again until a searcher is added with
+    /// [`add_searcher`](Self::add_searcher).
+    pub fn shutdown(&mut self) {
+        self.cancellation.cancel();
+        while self.remove_searcher() {}
+    }
+}
+
+impl<G: game::GameState, H> Drop for MeanMax<G, H> {
+    fn drop(&mut self) {
+        self.shutdown();
+    }
+}
+
+impl<G, H> super::Bot<G> for MeanMax<G, H>
+where
+    G: game::GameState + Send + Clone + Display + 'static,
+    G::Outcome:
+        game::DiscreteDistribution<T = G> + game::Canonical + Hash + Ord + Clone + Display + Send,
+    G::Action: game::Discrete + Send + Clone + Display + PartialEq,
+    searcher::Value: From<G::Reward> + From<<G::Outcome as game::DiscreteDistribution>::Weight>,
+    H: super::heuristic::Heuristic<G::Outcome, searcher::Value> + 'static,
+    <G::Outcome as game::DiscreteDistribution>::Weight: Debug,
+{
+    fn decide_until(
+        &mut self,
+        state: &G,
+        constraint: searcher::SearchConstraint,
+    ) -> searcher::Decision<G::Action> {
+        MeanMax::decide_until(self, state, constraint).0
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::builder::MeanMaxBuilder;
+    use super::max_depth::MaxDepth;
+    use super::searcher::{Decision, Sampling, SearchConstraint, Value};
+    use super::{MeanMax, Parallelism};
+    use crate::bots::heuristic::{Heuristic, TwentyFortyEightHeuristic};
+    use crate::game::twenty_forty_eight::{board::Direction, Outcome, State};
+    use std::sync::atomic::{AtomicUsize, Ordering};
+    use std::time::{Duration, Instant};
+
+    /// Evaluates the boards of up to two tiles, panics on the others.
+    #[derive(Default)]
+    struct TwoTilesHeuristic(TwentyFortyEightHeuristic<4, 4>);
+
+    impl Heuristic<Outcome<4, 4>, Value> for TwoTilesHeuristic {
+        fn eval(&self, state: &Outcome<4, 4>) -> Value {
+            assert!(state.cells.count_empty() >= 14, "broken heuristic");
+            self.0.eval(state)
+        }
+
+        fn update(&mut self, _state: Outcome<4, 4>, _eval: Value) {}
+    }
+
+    #[test]
+    fn test_analyze() {
+        let mut ai = MeanMax::new();
+        let state = State::from_cells([[1, 0, 0, 0], [2, 0, 0, 0], [0, 0, 0, 0], [0, 0, 0, 0]]);
+        let constraint = SearchConstraint::new().with_max_depth(MaxDepth::new(2));
+
+        let analysis = ai.analyze(&state, constraint);
+        assert_eq!(analysis.actions.len(), 4);
+        for action in &analysis.actions {
+            let is_legal = matches!(action.action, Direction::Right | Direction::Down);
+            assert_eq!(action.is_legal, is_legal);
+            assert_eq!(action.evaluation.is_some(), is_legal, "{analysis}");
+        }
+
+        let Decision::Act(best) = &analysis.decision else {
+            panic!("expected an action for:\n{state}");
+        };
+        assert_eq!(analysis.ranked()[0].action, best.action);
+        assert!(analysis.ranked().iter().all(|action| action.nodes > 0));
+
+        let stats = &analysis.stats;
+        assert_eq!(stats.nodes, best.nodes);
+        assert_eq!(stats.deepest_iteration(), Some(MaxDepth::new(2)));
+        assert!(stats.heuristic_calls > 0);
+        assert!(stats.total_cache_misses() > 0);
+
+        // The second search finds the evaluations of the first one.
+        let stats = ai.analyze(&state, constraint).stats;
+        assert!(stats.total_cache_hits() > 0);
+        assert_eq!(stats.heuristic_calls, 0);
+
+        let (decision, stats) = ai.decide_until(&state, constraint);
+        assert_eq!(decision.nodes(), stats.nodes);
+        assert_eq!(stats.deepest_iteration(), Some(MaxDepth::new(2)));
+    }
+
+    #[test]
+    fn test_split_work() {
+        let state = State::from_cells([[1, 0, 0, 0], [2, 0, 0, 0], [0, 1, 0, 0], [0, 0, 0, 0]]);
+        let constraint = SearchConstraint::new().with_max_depth(MaxDepth::new(2));
+        let sampled = constraint.with_sampling(Sampling::new(4).with_seed(1));
+
+        let mut split = MeanMax::new();
+        split.parallelism = Parallelism::SplitWork;
+
+        for constraint in [sampled, constraint] {
+            let mut staggered = MeanMax::new();
+            let expected = staggered.analyze(&state, constraint);
+            // The sampled search first, as it would reuse the exact evaluations.
+            let analysis = split.analyze(&state, constraint);
+
+            assert_eq!(analysis.decision.eval().min_depth, MaxDepth::new(2));
+            for (action, expected) in analysis.ranked().iter().zip(expected.ranked()) {
+                let (eval, expected_eval) = (&action.eval, &expected.eval);
+                assert_eq!(action.action, expected.action);
+                assert!((eval.value - expected_eval.value).abs() < 1e-3 * expected_eval.value);
+                assert_eq!(eval.min_depth, expected_eval.min_depth);
+                assert_eq!(eval.sampled, expected_eval.sampled);
+                assert_eq!(eval.samples, expected_eval.samples);
+                let tolerance = 1e-3 * expected_eval.variance.max(1.0);
+                assert!((eval.variance - expected_eval.variance).abs() < tolerance);
+            }
+        }
+
+        let deadline = Instant::now() + Duration::from_millis(50);
+        let (decision, _) =
+            split.decide_until(&state, SearchConstraint::new().with_deadline(deadline));
+        assert!(decision.eval().min_depth >= MaxDepth::new(2));
+    }
+
+    #[test]
+    fn test_node_budget() {
+        let state = State::from_cells([[1, 0, 0, 0], [2, 0, 0, 0], [0, 1, 0, 0], [0, 0, 0, 0]]);
+        let constraint = SearchConstraint::new().with_max_nodes(20_000);
+
+        for parallelism in [Parallelism::StaggeredDepths, Parallelism::SplitWork] {
+            // The same search with any number of threads, twice with one thread.
+            let searches: Vec<_> = [1, 1, 2, 4]
+                .into_iter()
+                .map(|threads| {
+                    let mut ai = MeanMax::builder()
+                        .with_threads(threads)
+                        .with_parallelism(parallelism)
+                        .build()
+                        .unwrap();
+                    let analysis = ai.analyze(&state, constraint);
+                    (analysis.decision, analysis.stats.nodes)
+                })
+                .collect();
+
+            let (decision, nodes) = &searches[0];
+            assert!(*nodes <= 20_000, "{parallelism:?}: {nodes} nodes");
+            assert!(decision.eval().min_depth >= MaxDepth::new(2), "{decision}");
+            assert!(
+                searches.iter().all(|search| search == &searches[0]),
+                "{parallelism:?}: {searches:?}"
+            );
+        }
+    }
+
+    #[test]
+    fn test_deep_initial_depth() {
+        let state = State::from_cells([[0, 0, 0, 0], [0, 0, 0, 0], [0, 1, 0, 0], [0, 0, 0, 1]]);
+
+        for parallelism in [Parallelism::StaggeredDepths, Parallelism::SplitWork] {
+            let mut ai = MeanMax::builder()
+                .with_initial_depth(MaxDepth::new(8))
+                .with_parallelism(parallelism)
+                .build()
+                .unwrap();
+
+            // The initial search would take much longer than the deadline.
+            let start = Instant::now();
+            let deadline = start + Duration::from_millis(20);
+            let analysis = ai.analyze(&state, SearchConstraint::new().with_deadline(deadline));
+
+            assert!(
+                start.elapsed() < Duration::from_millis(500),
+                "{parallelism:?}"
+            );
+            // Only the search of the heuristic alone completes, and gives an action.
+            let stats = &analysis.stats;
+            assert!(
+                stats
+                    .iterations
+                    .iter()
+                    .all(|iteration| iteration.completed == (iteration.depth == MaxDepth::new(0))),
+                "{parallelism:?}: {stats:?}"
+            );
+            assert!(
+                matches!(analysis.decision, Decision::Act(_)),
+                "{parallelism:?}: {}",
+                analysis.decision
+            );
+        }
+    }
+
+    #[test]
+    fn test_cancellation() {
+        let mut ai = MeanMax::new();
+        let state = State::from_cells([[0, 0, 0, 0], [0, 0, 0, 0], [0, 1, 0, 0], [0, 0, 0, 1]]);
+
+        let token = ai.cancellation_token();
+        let canceller = std::thread::spawn(move || {
+            std::thread::sleep(Duration::from_millis(50));
+            token.cancel();
+        });
+
+        // The search would never end without the cancellation.
+        let start = Instant::now();
+        let (decision, _) = ai.decide_until(&state, SearchConstraint::new());
+        canceller.join().unwrap();
+
+        assert!(start.elapsed() < Duration::from_secs(5));
+        assert!(matches!(decision, Decision::Act(_)));
+
+        // The next search is not cancelled.
+        let state = State::from_cells([[1, 0, 0, 0], [0, 0, 0, 0], [0, 2, 0, 0], [0, 0, 0, 1]]);
+        let constraint = SearchConstraint::new().with_max_depth(MaxDepth::new(1));
+        let (decision, _) = ai.decide_until(&state, constraint);
+        assert!(decision.eval().min_depth >= MaxDepth::new(1));
+    }
+
+    #[test]
+    fn test_searcher_panic() {
+        let created = AtomicUsize::new(0);
+        let mut ai = MeanMax::builder()
+            .with_threads(2)
+            .with_heuristic_factory(move || {
+                // The first searcher breaks as it starts.
+                assert_ne!(
+                    created.fetch_add(1, Ordering::Relaxed),
+                    0,
+                    "broken heuristic"
+                );
+                TwentyFortyEightHeuristic::new()
+            })
+            .build()
+            .unwrap();
+
+        let state = State::from_cells([[1, 0, 0, 0], [2, 0, 0, 0], [0, 1, 0, 0], [0, 0, 0, 0]]);
+        let constraint = SearchConstraint::new().with_max_depth(MaxDepth::new(2));
+        for _ in 0..2 {
+            let (decision, _) = ai.decide_until(&state, constraint);
+            assert_eq!(decision.eval().min_depth, MaxDepth::new(2));
+        }
+        assert_eq!(ai.searcher_threads.len(), 2);
+
+        ai.shutdown();
+        assert!(ai.searcher_threads.is_empty());
+        assert!(!ai.remove_searcher());
+
+        ai.add_searcher();
+        let (decision, _) = ai.decide_until(&state, constraint);
+        assert!(matches!(decision, Decision::Act(_)));
+
+        // A search that breaks every time is stopped, the shallower searches give the decision.
+        let state = State::from_cells([[1, 0, 0, 0], [2, 0, 0, 0], [0, 0, 0, 0], [0, 0, 0, 0]]);
+        for parallelism in [Parallelism::StaggeredDepths, Parallelism::SplitWork] {
+            let mut ai = MeanMaxBuilder::<_, TwoTilesHeuristic>::new()
+                .with_threads(2)
+                .with_parallelism(parallelism)
+                .build()
+                .unwrap();
+
+            let (decision, stats) = ai.decide_until(&state, SearchConstraint::new());
+            assert_eq!(
+                decision.eval().min_depth,
+                MaxDepth::new(0),
+                "{parallelism:?}"
+            );
+            assert_eq!(stats.deepest_iteration(), Some(MaxDepth::new(0)));
+            assert_eq!(ai.searcher_threads.len(), 2);
+        }
+
+        // The only searcher breaks, its replacement searches instead.
+        let state = State::from_cells([[1, 0, 0, 0], [2, 0, 0, 0], [0, 1, 0, 0], [0, 0, 0, 0]]);
+        for parallelism in [Parallelism::StaggeredDepths, Parallelism::SplitWork] {
+            let created = AtomicUsize::new(0);
+            let mut ai = MeanMax::builder()
+                .with_threads(1)
+                .with_parallelism(parallelism)
+                .with_heuristic_factory(move || {
+                    assert_ne!(
+                        created.fetch_add(1, Ordering::Relaxed),
+                        0,
+                        "broken heuristic"
+                    );
+                    TwentyFortyEightHeuristic::new()
+                })
+                .build()
+                .unwrap();
+
+            let (decision, _) = ai.decide_until(&state, constraint);
+            assert_eq!(
+                decision.eval().min_depth,
+                MaxDepth::new(2),
+                "{parallelism:?}"
+            );
+        }
     }
 }
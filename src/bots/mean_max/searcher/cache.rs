@@ -1,15 +1,26 @@
 use std::{
     collections::{BTreeSet, HashMap},
-    hash::Hash,
+    fmt::Display,
+    hash::{BuildHasher, Hash, RandomState},
+    marker::PhantomData,
+    mem::size_of,
+    num::NonZeroU64,
+    sync::{Arc, Mutex, MutexGuard, PoisonError},
 };
 
 pub struct PriorityCache<K, V, P> {
     priorities: BTreeSet<(P, K)>,
-    values: HashMap<K, V>,
+    values: HashMap<K, (V, P)>,
     capacity: usize,
 }
 
 impl<K, V, P> PriorityCache<K, V, P> {
+    /// Control bytes past the end of the hash table, the width of a SIMD group.
+    const TABLE_GROUP_WIDTH: usize = 16;
+    /// Keys of a node of the priority tree, and the fewest keys of a node other than the root.
+    const TREE_NODE_KEYS: usize = 11;
+    const TREE_NODE_MIN_KEYS: usize = 5;
+
     pub fn new(capacity: usize) -> Self {
         Self {
             priorities: BTreeSet::new(),
@@ -17,14 +28,94 @@ impl<K, V, P> PriorityCache<K, V, P> {
             capacity,
         }
     }
+
+    /// Creates a cache with as many values as fit in `bytes`, see
+    /// [`memory_usage`](Self::memory_usage).
+    ///
+    /// The hash table is allocated upfront with room for twice the values, so the tombstones
+    /// left by evictions are cleared in place instead of growing the table past the budget.
+    pub fn with_byte_budget(bytes: usize) -> Self {
+        let capacity = Self::capacity_for(bytes);
+
+        Self {
+            priorities: BTreeSet::new(),
+            values: HashMap::with_capacity(2 * capacity),
+            capacity,
+        }
+    }
+
+    /// Number of values of a cache created with a budget of `bytes`.
+    pub fn capacity_for(bytes: usize) -> usize {
+        // The table has a power of two of buckets, 7/8 of them can be full.
+        std::iter::successors(Some(4usize), |buckets| buckets.checked_mul(2))
+            .map(|buckets| (buckets, Self::table_bytes(buckets)))
+            .take_while(|&(_, table_bytes)| table_bytes <= bytes)
+            .map(|(buckets, table_bytes)| {
+                let full_buckets = if buckets < 8 {
+                    buckets - 1
+                } else {
+                    buckets / 8 * 7
+                };
+                (full_buckets / 2).min((bytes - table_bytes) / Self::tree_entry_bytes())
+            })
+            .max()
+            .unwrap_or(0)
+    }
+
+    /// Bytes allocated by the cache.
+    ///
+    /// The hash table is measured from its capacity, while the priority tree is counted at the
+    /// worst case of its nodes, so this is an upper bound.
+    pub fn memory_usage(&self) -> usize {
+        let capacity = self.values.capacity();
+        let buckets = match capacity {
+            0 => 0,
+            1..8 => capacity + 1,
+            _ => capacity / 7 * 8,
+        };
+
+        Self::table_bytes(buckets) + self.priorities.len() * Self::tree_entry_bytes()
+    }
+
+    pub fn usage(&self) -> CacheUsage {
+        CacheUsage {
+            entries: self.values.len(),
+            capacity: self.capacity,
+            bytes: self.memory_usage(),
+        }
+    }
+
+    /// Bytes of a hash table with `buckets` buckets: a slot and a control byte per bucket.
+    fn table_bytes(buckets: usize) -> usize {
+        match buckets {
+            0 => 0,
+            _ => buckets * (size_of::<(K, (V, P))>() + 1) + Self::TABLE_GROUP_WIDTH,
+        }
+    }
+
+    /// Bytes of the priority tree per key, when every node has the fewest keys.
+    fn tree_entry_bytes() -> usize {
+        let pointer = size_of::<usize>();
+        // Parent pointer, index in the parent and number of keys
+        let leaf = pointer + 4 + Self::TREE_NODE_KEYS * size_of::<(P, K)>();
+        let internal = leaf + (Self::TREE_NODE_KEYS + 1) * pointer;
+
+        // An internal node has one more child than keys.
+        let leaves_per_internal = Self::TREE_NODE_MIN_KEYS + 1;
+        (leaf + internal / leaves_per_internal).div_ceil(Self::TREE_NODE_MIN_KEYS)
+    }
 }
 
 impl<K, V, P> PriorityCache<K, V, P>
 where
     K: Hash + Ord + Clone,
-    P: Ord,
+    P: Ord + Clone,
 {
     pub fn put(&mut self, key: K, value: V, priority: P) {
+        if let Some((_value, old_priority)) = self.values.remove(&key) {
+            self.priorities.remove(&(old_priority, key.clone()));
+        }
+
         while self.values.len() >= self.capacity {
             let Some((_priority, key)) = self.priorities.pop_first() else {
                 return;
@@ -32,8 +123,8 @@ where
             self.values.remove(&key);
         }
 
-        self.priorities.insert((priority, key.clone()));
-        self.values.insert(key, value);
+        self.priorities.insert((priority.clone(), key.clone()));
+        self.values.insert(key, (value, priority));
     }
 }
 
@@ -42,6 +133,574 @@ where
     K: Hash + Eq,
 {
     pub fn get(&self, key: &K) -> Option<&V> {
-        self.values.get(key)
+        self.values.get(key).map(|(value, _priority)| value)
+    }
+
+    pub fn priority(&self, key: &K) -> Option<&P> {
+        self.values.get(key).map(|(_value, priority)| priority)
+    }
+
+    pub fn len(&self) -> usize {
+        self.values.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.values.is_empty()
+    }
+}
+
+/// How full a cache is, and how much memory it takes.
+#[derive(Copy, Clone, Debug, Default, PartialEq, Eq)]
+pub struct CacheUsage {
+    pub entries: usize,
+    pub capacity: usize,
+    /// Bytes allocated by the cache, see [`PriorityCache::memory_usage`] and
+    /// [`TranspositionTable::memory_usage`].
+    pub bytes: usize,
+}
+
+impl std::ops::Add for CacheUsage {
+    type Output = Self;
+
+    fn add(self, rhs: Self) -> Self::Output {
+        Self {
+            entries: self.entries + rhs.entries,
+            capacity: self.capacity + rhs.capacity,
+            bytes: self.bytes + rhs.bytes,
+        }
+    }
+}
+
+impl std::iter::Sum for CacheUsage {
+    fn sum<I: Iterator<Item = Self>>(iter: I) -> Self {
+        iter.fold(Self::default(), std::ops::Add::add)
+    }
+}
+
+impl Display for CacheUsage {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let mebibytes = self.bytes as f64 / f64::from(1 << 20);
+        write!(
+            f,
+            "{}/{} entries in {mebibytes:.1} MiB",
+            self.entries, self.capacity
+        )
+    }
+}
+
+/// A [`PriorityCache`] that can be shared between threads.
+///
+/// The keys are split between shards that are locked independently, so threads rarely wait for
+/// each other.
+pub struct SharedCache<K, V, P> {
+    shards: Box<[Mutex<PriorityCache<K, V, P>>]>,
+    hasher: RandomState,
+}
+
+impl<K, V, P> SharedCache<K, V, P> {
+    const SHARDS: usize = 64;
+
+    /// Creates a cache that holds about `capacity` values in total.
+    pub fn new(capacity: usize) -> Self {
+        let shard_capacity = capacity.div_ceil(Self::SHARDS).max(1);
+
+        Self {
+            shards: (0..Self::SHARDS)
+                .map(|_| Mutex::new(PriorityCache::new(shard_capacity)))
+                .collect(),
+            hasher: RandomState::new(),
+        }
+    }
+
+    /// Creates a cache that fits in `bytes`, see [`PriorityCache::with_byte_budget`].
+    pub fn with_byte_budget(bytes: usize) -> Self {
+        let shard_bytes = bytes.saturating_sub(Self::overhead_bytes()) / Self::SHARDS;
+
+        Self {
+            shards: (0..Self::SHARDS)
+                .map(|_| Mutex::new(PriorityCache::with_byte_budget(shard_bytes)))
+                .collect(),
+            hasher: RandomState::new(),
+        }
+    }
+
+    /// Number of values of a cache created with a budget of `bytes`.
+    pub fn capacity_for(bytes: usize) -> usize {
+        let shard_bytes = bytes.saturating_sub(Self::overhead_bytes()) / Self::SHARDS;
+        PriorityCache::<K, V, P>::capacity_for(shard_bytes) * Self::SHARDS
+    }
+
+    /// Bytes of the shards themselves, without their allocations.
+    fn overhead_bytes() -> usize {
+        Self::SHARDS * size_of::<Mutex<PriorityCache<K, V, P>>>()
+    }
+
+    pub fn len(&self) -> usize {
+        self.usage().entries
+    }
+
+    pub fn usage(&self) -> CacheUsage {
+        let shards: CacheUsage = self
+            .shards
+            .iter()
+            .map(|shard| shard.lock().unwrap_or_else(PoisonError::into_inner).usage())
+            .sum();
+
+        CacheUsage {
+            bytes: shards.bytes + Self::overhead_bytes(),
+            ..shards
+        }
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+}
+
+impl<K: Hash, V, P> SharedCache<K, V, P> {
+    fn shard(&self, key: &K) -> &Mutex<PriorityCache<K, V, P>> {
+        let hash = self.hasher.hash_one(key);
+        &self.shards[hash as usize % self.shards.len()]
+    }
+}
+
+impl<K, V, P> SharedCache<K, V, P>
+where
+    K: Hash + Ord + Clone,
+    P: Ord + Clone,
+{
+    /// Inserts the value unless the key already has a value with a higher priority (e.g. another
+    /// thread searched it deeper).
+    pub fn put(&self, key: K, value: V, priority: P) {
+        let mut shard = self
+            .shard(&key)
+            .lock()
+            .unwrap_or_else(PoisonError::into_inner);
+        if shard.priority(&key).is_some_and(|old| *old > priority) {
+            return;
+        }
+
+        shard.put(key, value, priority);
+    }
+}
+
+impl<K, V, P> SharedCache<K, V, P>
+where
+    K: Hash + Eq,
+    V: Clone,
+{
+    pub fn get(&self, key: &K) -> Option<V> {
+        self.shard(key)
+            .lock()
+            .unwrap_or_else(PoisonError::into_inner)
+            .get(key)
+            .cloned()
+    }
+}
+
+/// A cache shared by the searcher threads, so the searchers can use any implementation.
+pub trait Cache<K, V, P>: Send + Sync {
+    fn get(&self, key: &K) -> Option<V>;
+
+    /// Inserts the value unless the key already has a value with a higher priority.
+    fn put(&self, key: K, value: V, priority: P);
+
+    fn usage(&self) -> CacheUsage;
+
+    fn len(&self) -> usize {
+        self.usage().entries
+    }
+
+    fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+}
+
+impl<K, V, P> Cache<K, V, P> for SharedCache<K, V, P>
+where
+    K: Hash + Ord + Clone + Send,
+    V: Clone + Send,
+    P: Ord + Clone + Send,
+{
+    fn get(&self, key: &K) -> Option<V> {
+        SharedCache::get(self, key)
+    }
+
+    fn put(&self, key: K, value: V, priority: P) {
+        SharedCache::put(self, key, value, priority);
+    }
+
+    fn usage(&self) -> CacheUsage {
+        SharedCache::usage(self)
+    }
+}
+
+/// Implementations of [`Cache`].
+#[derive(Copy, Clone, Debug, Default, PartialEq, Eq)]
+pub enum CacheKind {
+    /// [`SharedCache`], evicts the values with the lowest priority of the whole cache.
+    #[default]
+    Priority,
+    /// [`TranspositionTable`], evicts the value with the lowest priority of a small bucket in
+    /// constant time.
+    Transposition,
+}
+
+impl CacheKind {
+    /// Creates a cache of this kind that fits in `bytes`.
+    pub fn with_byte_budget<K, V, P>(self, bytes: usize) -> Arc<dyn Cache<K, V, P>>
+    where
+        K: Hash + Ord + Clone + Send + 'static,
+        V: Clone + Send + 'static,
+        P: Ord + Clone + Send + 'static,
+    {
+        match self {
+            CacheKind::Priority => Arc::new(SharedCache::with_byte_budget(bytes)),
+            CacheKind::Transposition => Arc::new(TranspositionTable::with_byte_budget(bytes)),
+        }
+    }
+
+    /// Creates a cache of this kind that holds about `capacity` values.
+    pub fn with_capacity<K, V, P>(self, capacity: usize) -> Arc<dyn Cache<K, V, P>>
+    where
+        K: Hash + Ord + Clone + Send + 'static,
+        V: Clone + Send + 'static,
+        P: Ord + Clone + Send + 'static,
+    {
+        match self {
+            CacheKind::Priority => Arc::new(SharedCache::new(capacity)),
+            CacheKind::Transposition => Arc::new(TranspositionTable::with_capacity(capacity)),
+        }
+    }
+
+    /// Number of values of a cache of this kind created with a budget of `bytes`.
+    pub fn capacity_for<K, V, P>(self, bytes: usize) -> usize {
+        match self {
+            CacheKind::Priority => SharedCache::<K, V, P>::capacity_for(bytes),
+            CacheKind::Transposition => TranspositionTable::<K, V, P>::capacity_for(bytes),
+        }
+    }
+}
+
+/// A table of small buckets with constant time lookups and insertions.
+///
+/// A key can only be in the bucket picked by its hash, and a full bucket evicts its value with
+/// the lowest priority. Only the hash of a key is stored to identify it, so another key is only
+/// mistaken for it if their 63 bits match. The buckets are locked by stripes of many buckets, so
+/// the locks take little memory.
+pub struct TranspositionTable<K, V, P> {
+    /// The stripes hold the same number of buckets, bucket `i` is in stripe `i % stripes`.
+    stripes: Box<[Stripe<V, P>]>,
+    hasher: RandomState,
+    keys: PhantomData<fn(&K)>,
+}
+
+struct Slot<V, P> {
+    /// Hash of the key with its lowest bit set, so an empty slot takes no space.
+    hash: NonZeroU64,
+    value: V,
+    priority: P,
+}
+
+/// Values in a bucket of a [`TranspositionTable`].
+const BUCKET_WAYS: usize = 4;
+
+struct Bucket<V, P> {
+    slots: [Option<Slot<V, P>>; BUCKET_WAYS],
+}
+
+/// Buckets of a [`TranspositionTable`] locked together.
+type Stripe<V, P> = Mutex<Box<[Bucket<V, P>]>>;
+type StripeGuard<'a, V, P> = MutexGuard<'a, Box<[Bucket<V, P>]>>;
+
+impl<V, P> Default for Bucket<V, P> {
+    fn default() -> Self {
+        Self {
+            slots: std::array::from_fn(|_| None),
+        }
+    }
+}
+
+impl<K, V, P> TranspositionTable<K, V, P> {
+    /// Most stripes of a table, enough for the searcher threads to rarely wait for each other.
+    const STRIPES: usize = 1024;
+
+    /// Creates a table with as many buckets as fit in `bytes`, all allocated upfront.
+    pub fn with_byte_budget(bytes: usize) -> Self {
+        let (stripes, stripe_buckets) = Self::layout(bytes);
+
+        Self {
+            stripes: (0..stripes)
+                .map(|_| Mutex::new((0..stripe_buckets).map(|_| Bucket::default()).collect()))
+                .collect(),
+            hasher: RandomState::new(),
+            keys: PhantomData,
+        }
+    }
+
+    /// Creates a table with room for at least `capacity` values, all allocated upfront.
+    pub fn with_capacity(capacity: usize) -> Self {
+        let buckets = capacity.div_ceil(BUCKET_WAYS).max(1);
+        let stripes = buckets.min(Self::STRIPES);
+        let bytes = stripes * size_of::<Stripe<V, P>>()
+            + buckets.next_multiple_of(stripes) * size_of::<Bucket<V, P>>();
+
+        Self::with_byte_budget(bytes)
+    }
+
+    /// Number of values of a table created with a budget of `bytes`.
+    pub fn capacity_for(bytes: usize) -> usize {
+        let (stripes, stripe_buckets) = Self::layout(bytes);
+        stripes * stripe_buckets * BUCKET_WAYS
+    }
+
+    /// Stripes and buckets per stripe of a table that fits in `bytes`.
+    fn layout(bytes: usize) -> (usize, usize) {
+        let stripe_bytes = size_of::<Stripe<V, P>>();
+        let bucket_bytes = size_of::<Bucket<V, P>>();
+
+        // One stripe per bucket for tiny tables
+        let stripes = (bytes / (stripe_bytes + bucket_bytes)).min(Self::STRIPES);
+        let stripe_buckets = match stripes {
+            0 => 0,
+            _ => (bytes - stripes * stripe_bytes) / bucket_bytes / stripes,
+        };
+
+        (stripes, stripe_buckets)
+    }
+
+    /// Bytes allocated by the table, which never change.
+    pub fn memory_usage(&self) -> usize {
+        let buckets: usize = self.usage_by_stripe(|stripe| stripe.len());
+        self.stripes.len() * size_of::<Stripe<V, P>>() + buckets * size_of::<Bucket<V, P>>()
+    }
+
+    pub fn usage(&self) -> CacheUsage {
+        let entries = self.usage_by_stripe(|stripe| {
+            stripe
+                .iter()
+                .map(|bucket| bucket.slots.iter().flatten().count())
+                .sum()
+        });
+        let buckets = self.usage_by_stripe(|stripe| stripe.len());
+
+        CacheUsage {
+            entries,
+            capacity: buckets * BUCKET_WAYS,
+            bytes: self.memory_usage(),
+        }
+    }
+
+    /// Sums `count` over the buckets of every stripe.
+    fn usage_by_stripe(&self, count: impl Fn(&[Bucket<V, P>]) -> usize) -> usize {
+        self.stripes
+            .iter()
+            .map(|stripe| count(&stripe.lock().unwrap_or_else(PoisonError::into_inner)))
+            .sum()
+    }
+}
+
+impl<K: Hash, V, P> TranspositionTable<K, V, P> {
+    /// Locks the stripe of the bucket of `key`, and returns it with the index of the bucket in
+    /// the stripe and the hash identifying `key`. `None` if the table has no bucket.
+    fn locate(&self, key: &K) -> Option<(StripeGuard<'_, V, P>, usize, NonZeroU64)> {
+        let hash = self.hasher.hash_one(key);
+        let stripe = self
+            .stripes
+            .get(hash as usize % self.stripes.len().max(1))?;
+
+        let stripe = stripe.lock().unwrap_or_else(PoisonError::into_inner);
+        // The high bits of the hash pick the bucket, the low ones the stripe.
+        let index = (u128::from(hash) * stripe.len() as u128) >> 64;
+        Some((stripe, index as usize, NonZeroU64::MIN | hash))
+    }
+
+    fn position(bucket: &Bucket<V, P>, hash: NonZeroU64) -> Option<usize> {
+        bucket
+            .slots
+            .iter()
+            .position(|slot| slot.as_ref().is_some_and(|slot| slot.hash == hash))
+    }
+}
+
+impl<K, V, P> Cache<K, V, P> for TranspositionTable<K, V, P>
+where
+    K: Hash,
+    V: Clone + Send,
+    P: Ord + Send,
+{
+    fn get(&self, key: &K) -> Option<V> {
+        let (stripe, index, hash) = self.locate(key)?;
+        let bucket = &stripe[index];
+
+        let position = Self::position(bucket, hash)?;
+        bucket.slots[position]
+            .as_ref()
+            .map(|slot| slot.value.clone())
+    }
+
+    fn put(&self, key: K, value: V, priority: P) {
+        let Some((mut stripe, index, hash)) = self.locate(&key) else {
+            return;
+        };
+        let bucket = &mut stripe[index];
+
+        let position = match Self::position(bucket, hash) {
+            Some(position) => {
+                if bucket.slots[position]
+                    .as_ref()
+                    .is_some_and(|slot| slot.priority > priority)
+                {
+                    return;
+                }
+
+                position
+            }
+            // An empty slot, or else the lowest priority of the bucket
+            None => bucket
+                .slots
+                .iter()
+                .enumerate()
+                .min_by_key(|&(_, slot)| slot.as_ref().map(|slot| &slot.priority))
+                .map(|(position, _)| position)
+                .expect("buckets have slots"),
+        };
+
+        bucket.slots[position] = Some(Slot {
+            hash,
+            value,
+            priority,
+        });
+    }
+
+    fn usage(&self) -> CacheUsage {
+        TranspositionTable::usage(self)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{Bucket, Cache, PriorityCache, SharedCache, Slot, Stripe, TranspositionTable};
+    use std::sync::Arc;
+
+    #[test]
+    fn test_transposition_table() {
+        let table = TranspositionTable::with_byte_budget(1);
+        assert_eq!(table.usage().capacity, 0);
+        table.put(1, 1, 1);
+        assert_eq!(table.get(&1), None);
+
+        // A single bucket
+        let bucket_bytes = size_of::<Stripe<u32, u32>>() + size_of::<Bucket<u32, u32>>();
+        let table = TranspositionTable::with_byte_budget(bucket_bytes);
+        assert_eq!(table.usage().capacity, 4);
+        for key in 0..4 {
+            table.put(key, key, 10 + key);
+        }
+
+        // A higher priority is kept, the lowest priority of the bucket is evicted.
+        table.put(3, 0, 0);
+        assert_eq!(table.get(&3), Some(3));
+        table.put(4, 4, 20);
+        assert_eq!(table.get(&0), None);
+        assert!((1..5).all(|key| table.get(&key) == Some(key)));
+        assert_eq!(table.len(), 4);
+
+        // Only the slots grow with the budget, not the locks.
+        assert_eq!(
+            size_of::<Option<Slot<u32, u32>>>(),
+            size_of::<Slot<u32, u32>>()
+        );
+        for bytes in [100, 1 << 10, 100_000, 1 << 20] {
+            let table = TranspositionTable::<u64, u32, u32>::with_byte_budget(bytes);
+            (0..1 << 16).for_each(|key| table.put(key, 0, 0));
+
+            let usage = table.usage();
+            assert_eq!(
+                usage.capacity,
+                TranspositionTable::<u64, u32, u32>::capacity_for(bytes)
+            );
+            assert!(usage.bytes <= bytes, "{usage} over {bytes} bytes");
+            assert!(bytes < 100_000 || usage.bytes > bytes / 4 * 3, "{usage}");
+        }
+
+        for capacity in [0, 1, 5, 10_000, 100_000] {
+            let table = TranspositionTable::<u64, u32, u32>::with_capacity(capacity);
+            let usage = table.usage();
+            assert!(usage.capacity >= capacity.max(1), "{usage}");
+            assert!(usage.capacity < capacity + 4 * 1024, "{usage}");
+        }
+    }
+
+    #[test]
+    fn test_byte_budget() {
+        type Cache = PriorityCache<u64, [u8; 24], u32>;
+
+        for bytes in [0, 100, 1 << 10, 100_000, 1 << 20] {
+            let mut cache = Cache::with_byte_budget(bytes);
+            for key in 0..(1 << 16) {
+                cache.put(key, [0; 24], key as u32);
+            }
+
+            let usage = cache.usage();
+            assert_eq!(usage.capacity, Cache::capacity_for(bytes));
+            assert_eq!(usage.entries, usage.capacity);
+            assert!(usage.bytes <= bytes, "{usage} over {bytes} bytes");
+            // Most of the budget is used.
+            assert!(
+                bytes < 1 << 10 || usage.bytes > bytes / 4,
+                "{usage} of {bytes} bytes"
+            );
+        }
+
+        let cache = SharedCache::<u64, [u8; 24], u32>::with_byte_budget(1 << 20);
+        let usage = cache.usage();
+        assert_eq!(
+            usage.capacity,
+            SharedCache::<u64, [u8; 24], u32>::capacity_for(1 << 20)
+        );
+        assert!(usage.bytes <= 1 << 20);
+    }
+
+    #[test]
+    fn test_priority_cache() {
+        let mut cache = PriorityCache::new(2);
+        cache.put("a", 1, 5);
+        cache.put("b", 2, 1);
+        cache.put("b", 3, 7);
+
+        // The lowest priority is evicted first, updating a key replaces its priority.
+        cache.put("c", 4, 3);
+        assert_eq!(cache.get(&"a"), None);
+        assert_eq!(cache.get(&"b"), Some(&3));
+        assert_eq!(cache.get(&"c"), Some(&4));
+        assert_eq!(cache.len(), 2);
+    }
+
+    #[test]
+    fn test_shared_cache() {
+        let cache = Arc::new(SharedCache::new(1 << 12));
+
+        let threads: Vec<_> = (0..4u32)
+            .map(|thread| {
+                let cache = Arc::clone(&cache);
+                std::thread::spawn(move || {
+                    for key in 0..1000u32 {
+                        cache.put(key, thread, thread);
+                    }
+                })
+            })
+            .collect();
+
+        threads
+            .into_iter()
+            .for_each(|thread| thread.join().unwrap());
+
+        // The value with the highest priority wins, whatever the order of the threads.
+        assert_eq!(cache.len(), 1000);
+        assert!((0..1000).all(|key| cache.get(&key) == Some(3)));
+
+        cache.put(0, 0, 0);
+        assert_eq!(cache.get(&0), Some(3));
     }
 }
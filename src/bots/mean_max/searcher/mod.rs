@@ -1,14 +1,17 @@
 use super::logger::LoggerHandle;
 use super::max_depth::MaxDepth;
-use crate::accumulator::fraction::{Weighted, WeightedAverage};
 use crate::game::twenty_forty_eight;
 use crate::{bots::heuristic, game, utils};
 use std::any::Any;
 use std::fmt::Debug;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::Arc;
 use std::{cmp, fmt::Display, hash::Hash, time::Instant};
 use thiserror::Error;
 
 pub mod cache;
+pub mod stack;
+pub mod stats;
 
 pub type Value = f32;
 
@@ -18,15 +21,46 @@ pub struct Evaluation {
     pub value: Value,
 
     /// Minimum depth of searched tree ([MaxDepth::Unlimited] means this is the eval of a full search tree).
+    ///
+    /// The states cut off by [`cutoff`](Self::cutoff) are not searched at any depth, so they
+    /// don't count.
     pub min_depth: MaxDepth,
+
+    /// Some states of the tree were too unlikely to be searched to `min_depth`, see
+    /// [`SearchConstraint::min_probability`].
+    pub truncated: bool,
+
+    /// Probability below which the states of the tree were evaluated with the heuristic, relative
+    /// to the probability of the evaluated node, zero if the tree is not truncated.
+    pub cutoff: Value,
+
+    /// Some outcomes of the tree were estimated from a sample of their states, see
+    /// [`SearchConstraint::sampling`].
+    pub sampled: bool,
+
+    /// Fewest states drawn from a sampled outcome of the tree, [`u16::MAX`] if none were sampled.
+    pub samples: u16,
+
+    /// Variance of `value` due to the sampled outcomes, zero if none were sampled.
+    pub variance: Value,
 }
 
 impl Evaluation {
     const TERMINAL: Self = Evaluation {
         value: 0.0,
         min_depth: MaxDepth::Unlimited,
+        truncated: false,
+        cutoff: 0.0,
+        sampled: false,
+        samples: u16::MAX,
+        variance: 0.0,
     };
 
+    /// The evaluation is an estimate that a search without truncation or sampling can improve.
+    pub fn is_approximate(&self) -> bool {
+        self.truncated || self.sampled
+    }
+
     #[deprecated = "use `self.depth` instead"]
     pub fn fits_depth_bound(&self, bound: MaxDepth) -> bool {
         self.min_depth >= bound
@@ -42,6 +76,14 @@ impl Display for Evaluation {
 
         let precision = f.precision().unwrap_or(2);
         write!(f, " -> {value:.*}", precision, value = self.value)?;
+        if self.sampled {
+            write!(
+                f,
+                " ± {deviation:.*}",
+                precision,
+                deviation = self.variance.sqrt()
+            )?;
+        }
 
         Ok(())
     }
@@ -52,6 +94,9 @@ impl Display for Evaluation {
 pub struct EvaluatedAction<A> {
     pub eval: Evaluation,
     pub action: A,
+    /// Number of nodes expanded to evaluate the action, or by the whole search for the action of
+    /// its [`Decision`].
+    pub nodes: u64,
 }
 
 impl<A: Display> Display for EvaluatedAction<A> {
@@ -75,7 +120,22 @@ impl<A> Decision<A> {
         }
     }
 
-    fn max_by_eval(self, other: Self) -> Self {
+    /// Number of nodes expanded by the search that gave the decision.
+    pub fn nodes(&self) -> u64 {
+        match self {
+            Decision::Act(act) => act.nodes,
+            Decision::Resign => 0,
+        }
+    }
+
+    pub(super) fn with_nodes(mut self, nodes: u64) -> Self {
+        if let Decision::Act(act) = &mut self {
+            act.nodes = nodes;
+        }
+        self
+    }
+
+    pub(super) fn max_by_eval(self, other: Self) -> Self {
         std::cmp::max_by(self, other, |a, b| {
             a.eval()
                 .partial_cmp(&b.eval())
@@ -93,16 +153,134 @@ impl<A: Display> Display for Decision<A> {
     }
 }
 
+/// Evaluations of every action of the root state of a search, not only of the best one.
+#[derive(Clone, Debug)]
+pub struct RootAnalysis<A> {
+    /// Best of the evaluated actions.
+    pub decision: Decision<A>,
+    /// Every action of the game, in the order of [`Discrete::iter`](game::Discrete::iter).
+    pub actions: Vec<RootAction<A>>,
+    pub stats: stats::SearchStats,
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub struct RootAction<A> {
+    pub action: A,
+    pub is_legal: bool,
+    /// Evaluation of a legal action, `None` if the search stopped before evaluating it.
+    pub evaluation: Option<EvaluatedAction<A>>,
+}
+
+impl<A> RootAnalysis<A> {
+    pub fn new<G>(
+        state: &G,
+        decision: Decision<A>,
+        evaluated: Vec<EvaluatedAction<A>>,
+        stats: stats::SearchStats,
+    ) -> Self
+    where
+        G: game::GameState<Action = A>,
+        A: game::Discrete + PartialEq,
+    {
+        let mut evaluated: Vec<_> = evaluated.into_iter().map(Some).collect();
+
+        let actions = A::iter()
+            .map(|action| {
+                let evaluation = evaluated
+                    .iter_mut()
+                    .find(|evaluated| evaluated.as_ref().is_some_and(|e| e.action == action))
+                    .and_then(Option::take);
+
+                RootAction {
+                    is_legal: state.is_legal(&action),
+                    action,
+                    evaluation,
+                }
+            })
+            .collect();
+
+        Self {
+            decision,
+            actions,
+            stats,
+        }
+    }
+
+    /// The evaluated actions, from the best to the worst.
+    pub fn ranked(&self) -> Vec<&EvaluatedAction<A>> {
+        let mut ranked: Vec<_> = self
+            .actions
+            .iter()
+            .filter_map(|action| action.evaluation.as_ref())
+            .collect();
+
+        ranked.sort_by(|a, b| b.eval.value.total_cmp(&a.eval.value));
+        ranked
+    }
+}
+
+impl<A: Display> Display for RootAnalysis<A> {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        for action in &self.actions {
+            match (&action.evaluation, action.is_legal) {
+                (Some(evaluation), _) => writeln!(f, "{evaluation}")?,
+                (None, true) => writeln!(f, "{}: not evaluated", action.action)?,
+                (None, false) => writeln!(f, "{}: illegal", action.action)?,
+            }
+        }
+
+        Ok(())
+    }
+}
+
 #[derive(Debug, Error)]
 pub enum SearchError {
     #[error("search time exceeded the deadline")]
     TimeOut,
+    #[error("search expanded more nodes than its budget")]
+    NodeBudget,
+    #[error("search was cancelled")]
+    Cancelled,
+    #[error("searcher panicked")]
+    Panicked,
 }
 
-#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+/// Stops the searches that check it, it can be cloned and cancelled from any thread.
+#[derive(Clone, Debug, Default)]
+pub struct CancellationToken(Arc<AtomicBool>);
+
+impl CancellationToken {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn cancel(&self) {
+        self.0.store(true, Ordering::Relaxed);
+    }
+
+    pub fn is_cancelled(&self) -> bool {
+        self.0.load(Ordering::Relaxed)
+    }
+
+    /// Makes the token usable for the next search.
+    pub(super) fn reset(&self) {
+        self.0.store(false, Ordering::Relaxed);
+    }
+}
+
+#[derive(Copy, Clone, Debug, PartialEq)]
 pub struct SearchConstraint {
     pub deadline: Option<Instant>,
     pub max_depth: MaxDepth,
+    /// Maximum number of nodes expanded by a search, over all its iterations and threads. Unlike
+    /// a deadline the search gives the same result on any machine, with any number of threads,
+    /// since it only runs on one searcher thread.
+    pub max_nodes: Option<u64>,
+    /// States less likely than this to be reached from the root are evaluated with the heuristic
+    /// instead of being searched.
+    pub min_probability: Option<Value>,
+    /// Estimate outcomes from a sample of their states instead of searching all of them.
+    pub sampling: Option<Sampling>,
 }
 
 impl SearchConstraint {
@@ -110,6 +288,9 @@ impl SearchConstraint {
         Self {
             deadline: None,
             max_depth: MaxDepth::Unlimited,
+            max_nodes: None,
+            min_probability: None,
+            sampling: None,
         }
     }
 
@@ -135,6 +316,90 @@ impl SearchConstraint {
         self.max_depth = max_depth;
         self
     }
+
+    /// Limits the search to `max_nodes` nodes, see [`SearchConstraint::max_nodes`].
+    ///
+    /// The search then runs on a single searcher thread, whatever the number of threads of the
+    /// bot, so that it's reproducible.
+    #[must_use]
+    pub fn with_max_nodes(mut self, max_nodes: u64) -> Self {
+        self.max_nodes = Some(max_nodes);
+        self
+    }
+
+    #[must_use]
+    pub fn with_min_probability(mut self, min_probability: Value) -> Self {
+        self.min_probability = Some(min_probability);
+        self
+    }
+
+    #[must_use]
+    pub fn with_sampling(mut self, sampling: Sampling) -> Self {
+        self.sampling = Some(sampling);
+        self
+    }
+}
+
+/// Number of states sampled from each outcome, by the depth of the outcome in the search tree.
+///
+/// The states are drawn with replacement according to their weight, outcomes with fewer states
+/// than the number of samples are searched fully. The draws only depend on the seed and the
+/// outcome, so a sampled search gives the same result every time, on any thread.
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub struct Sampling {
+    samples: [u16; Self::DEPTHS],
+    seed: u64,
+}
+
+impl Sampling {
+    /// Depths with their own number of samples, deeper outcomes use the last one.
+    const DEPTHS: usize = 8;
+
+    /// Samples `samples` states at every depth, at least one.
+    pub fn new(samples: u16) -> Self {
+        Self {
+            samples: [samples.max(1); Self::DEPTHS],
+            seed: 0,
+        }
+    }
+
+    /// Samples `samples` states from the outcomes at `depth` and deeper, the outcomes of the
+    /// root actions are at depth 0.
+    #[must_use]
+    pub fn with_samples_from(mut self, depth: usize, samples: u16) -> Self {
+        let depth = depth.min(Self::DEPTHS - 1);
+        self.samples[depth..].fill(samples.max(1));
+        self
+    }
+
+    /// Draws different states, 0 by default.
+    #[must_use]
+    pub fn with_seed(mut self, seed: u64) -> Self {
+        self.seed = seed;
+        self
+    }
+
+    pub fn seed(&self) -> u64 {
+        self.seed
+    }
+
+    pub fn samples_at(&self, depth: usize) -> usize {
+        self.samples[depth.min(Self::DEPTHS - 1)].into()
+    }
+
+    /// Fewest samples of the outcomes at `depth` and deeper.
+    pub fn fewest_samples_from(&self, depth: usize) -> usize {
+        let samples = &self.samples[depth.min(Self::DEPTHS - 1)..];
+        samples.iter().copied().min().map_or(0, usize::from)
+    }
+
+    /// Sampling of a search from a state `levels` outcomes below the root.
+    pub(super) fn deeper(self, levels: usize) -> Self {
+        Self {
+            samples: std::array::from_fn(|depth| self.samples_at(depth + levels) as u16),
+            ..self
+        }
+    }
 }
 
 impl Default for SearchConstraint {
@@ -167,12 +432,27 @@ impl Display for SearchConstraint {
             }
         };
 
+        if let Some(max_nodes) = self.max_nodes {
+            write!(f, ", up to {max_nodes} nodes")?;
+        }
+
+        if let Some(min_probability) = self.min_probability {
+            write!(f, ", down to probability {min_probability}")?;
+        }
+
+        if let Some(sampling) = self.sampling {
+            write!(f, ", sampling {} states", sampling.samples_at(0))?;
+        }
+
         Ok(())
     }
 }
 
 #[derive(Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
-struct SearchPriority {
+pub(super) struct SearchPriority {
+    /// Exact evaluations are kept over approximate ones of any depth, since an approximate
+    /// evaluation can't answer an exact search.
+    exact: bool,
     depth: u8,
     step: u8,
 }
@@ -180,22 +460,47 @@ struct SearchPriority {
 pub(super) struct Searcher<Game: game::GameState, Heuristic> {
     pub depth_limit: MaxDepth,
     pub deadline: Option<Instant>,
+    /// See [`SearchConstraint::max_nodes`].
+    pub max_nodes: Option<u64>,
+    pub cancellation: Option<CancellationToken>,
+    /// Key the evaluation cache by the canonical form of the outcomes.
+    pub canonical_cache: bool,
+    /// See [`SearchConstraint::min_probability`].
+    pub min_probability: Option<Value>,
+    /// See [`SearchConstraint::sampling`].
+    pub sampling: Option<Sampling>,
     pub logger: LoggerHandle,
+    /// Statistics of the current search.
+    pub stats: stats::SearchStats,
     heuristic: Heuristic,
-    evaluation_cache: cache::PriorityCache<Game::Outcome, Evaluation, SearchPriority>,
+    evaluation_cache: Arc<EvaluationCache<Game>>,
 }
 
+/// Transposition table shared by the searchers, deeper evaluations are kept over shallower ones.
+pub(super) type EvaluationCache<G> =
+    dyn cache::Cache<<G as game::GameState>::Outcome, Evaluation, SearchPriority>;
+
 impl<G, H> Searcher<G, H>
 where
     G: game::GameState,
 {
-    pub fn new(heuristic: H, capacity: std::num::NonZeroUsize, logger: LoggerHandle) -> Self {
+    pub fn new(
+        heuristic: H,
+        evaluation_cache: Arc<EvaluationCache<G>>,
+        logger: LoggerHandle,
+    ) -> Self {
         Self {
             depth_limit: MaxDepth::Unlimited,
             deadline: None,
+            max_nodes: None,
+            cancellation: None,
+            canonical_cache: false,
+            min_probability: None,
+            sampling: None,
+            stats: stats::SearchStats::default(),
             heuristic,
             logger,
-            evaluation_cache: cache::PriorityCache::new(capacity.get()),
+            evaluation_cache,
         }
     }
 }
@@ -210,108 +515,99 @@ where
     G: game::GameState,
     G::Outcome: Hash + cmp::Eq,
 {
-    fn cached_evaluation(&mut self, outcome: &G::Outcome) -> OptionEvaluation {
-        let mut cached_eval = self.evaluation_cache.get(outcome).copied();
+    /// Returns the cached evaluation of the outcome, if it's as deep and precise as a search of
+    /// the outcome reached with `probability`, `depth` outcomes below the root, would be.
+    fn cached_evaluation(
+        &mut self,
+        outcome: &G::Outcome,
+        depth_limit: MaxDepth,
+        probability: Value,
+        depth: usize,
+    ) -> OptionEvaluation {
+        let mut cached_eval = self.evaluation_cache.get(outcome);
 
         if let Some(eval) = cached_eval.as_mut() {
-            if eval.min_depth < self.depth_limit {
+            // An approximate evaluation is only as good as a search that is as approximate.
+            let cutoff = self.relative_cutoff(probability);
+            let is_undersampled = eval.sampled
+                && self.sampling.is_none_or(|sampling| {
+                    usize::from(eval.samples) < sampling.fewest_samples_from(depth)
+                });
+            let is_too_approximate = eval.cutoff > cutoff || is_undersampled;
+            if eval.min_depth < depth_limit || is_too_approximate {
                 cached_eval = None;
             }
         }
 
         self.logger
-            .register_lookup_result(cached_eval.as_ref(), self.depth_limit);
+            .register_lookup_result(cached_eval.as_ref(), depth_limit);
+
+        let lookups = match cached_eval {
+            Some(_) => &mut self.stats.cache_hits,
+            None => &mut self.stats.cache_misses,
+        };
+        lookups.accumulate(depth_limit, 1);
 
         cached_eval
     }
+
+    /// Cutoff of the search relative to a node reached with `probability`, see
+    /// [`Evaluation::cutoff`].
+    fn relative_cutoff(&self, probability: Value) -> Value {
+        self.min_probability
+            .map_or(0.0, |min_probability| min_probability / probability)
+    }
 }
 
 impl<G, H> Searcher<G, H>
 where
     G: game::GameState + Clone + Display,
-    G::Outcome: game::DiscreteDistribution<T = G> + Hash + Ord + Clone + Display,
+    G::Outcome: game::DiscreteDistribution<T = G> + game::Canonical + Hash + Ord + Clone + Display,
     G::Action: game::Discrete + Clone + Display,
     Value: From<G::Reward> + From<<G::Outcome as game::DiscreteDistribution>::Weight>,
     H: heuristic::Heuristic<G::Outcome, Value>,
     <G::Outcome as game::DiscreteDistribution>::Weight: Debug,
 {
-    pub fn evaluate_state(&mut self, state: &G) -> EvaluationResult
-    where
-        <G as game::GameState>::Outcome: 'static,
-    {
+    fn is_past_deadline(&self) -> bool {
         let in_the_past = |instant: Instant| !instant.elapsed().is_zero();
-
-        if self.deadline.is_some_and(in_the_past) {
-            Err(SearchError::TimeOut)
-        } else {
-            self.make_decision(state).map(|decision| decision.eval())
-        }
+        self.deadline.is_some_and(in_the_past)
     }
 
-    pub fn make_decision(&mut self, state: &G) -> DecisionResult<G::Action>
-    where
-        <G as game::GameState>::Outcome: 'static,
-    {
-        let mut best_decision = Decision::Resign;
-
-        for action in <G::Action as game::Discrete>::iter() {
-            let (reward, outcome) = state.clone().outcome(action.clone());
-
-            // TODO: Make this iterative instead of recursive.
-            let eval = self.evaluate_outcome(outcome)?;
-            let eval = Evaluation {
-                value: eval.value + f32::from(reward),
-                min_depth: eval.min_depth,
-            };
-
-            let new_decision = Decision::Act(EvaluatedAction { eval, action });
-            best_decision = best_decision.max_by_eval(new_decision);
-        }
-
-        Ok(best_decision)
+    fn is_cancelled(&self) -> bool {
+        self.cancellation
+            .as_ref()
+            .is_some_and(CancellationToken::is_cancelled)
     }
 
-    fn evaluate_outcome(&mut self, outcome: G::Outcome) -> EvaluationResult
+    /// Evaluates a fully searched outcome from the evaluations of its states, and caches it.
+    fn finish_outcome(&mut self, frame: stack::OutcomeFrame<G>) -> Evaluation
     where
         <G as game::GameState>::Outcome: 'static,
     {
-        if outcome.clone().into_iter().next().is_none() {
-            return Ok(Evaluation::TERMINAL);
-        }
-
-        if let Some(evaluation) = self.cached_evaluation(&outcome) {
-            return Ok(evaluation);
-        }
-
-        // Decrease depth limit for the recursive call
-        self.depth_limit = match self.depth_limit - 1 {
-            Some(depth_limit) => depth_limit,
-            None => {
-                let evaluation = Evaluation {
-                    value: self.heuristic.eval(&outcome),
-                    min_depth: MaxDepth::new(0),
-                };
-
-                return Ok(evaluation);
-            }
+        let variance = frame.variance();
+        let cutoff = match frame.truncated {
+            true => self.relative_cutoff(frame.probability()),
+            false => 0.0,
         };
-
-        let mut mean_value = WeightedAverage::<Value, Value>::default();
-        let mut min_depth = MaxDepth::Unlimited;
-
-        for weighted in outcome.clone() {
-            let eval = self.evaluate_state(&weighted.value)?;
-
-            min_depth = std::cmp::min(eval.min_depth, min_depth);
-            mean_value += Weighted {
-                value: eval.value,
-                weight: f32::from(weighted.weight),
-            };
-        }
+        let stack::OutcomeFrame {
+            outcome,
+            cache_key,
+            mean_value,
+            min_depth,
+            truncated,
+            sampled,
+            samples,
+            ..
+        } = frame;
 
         let eval = Evaluation {
             value: mean_value.evaluate(),
             min_depth: min_depth + 1,
+            truncated,
+            cutoff,
+            sampled,
+            samples,
+            variance,
         };
 
         let step = {
@@ -319,24 +615,29 @@ where
                 <dyn Any>::downcast_ref::<twenty_forty_eight::Outcome<4, 4>>(&outcome)
             {
                 outcome_2048.cells.cells.as_flattened().iter().sum()
+            } else if let Some(outcome_2048) =
+                <dyn Any>::downcast_ref::<twenty_forty_eight::bitboard::Outcome>(&outcome)
+            {
+                // At most 16 nibbles of 15
+                outcome_2048.board.exponent_sum() as u8
             } else {
                 0
             }
         };
 
         if eval.min_depth.max_u8() > 2 {
-            self.heuristic.update(outcome.clone(), eval.value);
+            self.heuristic.update(outcome, eval.value);
         }
 
         let search_priority = SearchPriority {
             depth: eval.min_depth.max_u8(),
+            exact: !eval.is_approximate(),
             step,
         };
 
-        self.evaluation_cache.put(outcome, eval, search_priority);
+        self.evaluation_cache.put(cache_key, eval, search_priority);
 
-        self.depth_limit += 1;
-        Ok(eval)
+        eval
     }
 
     pub fn search(&mut self, task: super::Task<G>) -> super::SearchResult<G>
@@ -345,11 +646,282 @@ where
     {
         self.depth_limit = task.search_constraint.max_depth;
         self.deadline = task.search_constraint.deadline;
+        self.max_nodes = task.search_constraint.max_nodes;
+        self.cancellation = task.cancellation;
+        self.min_probability = task.search_constraint.min_probability;
+        self.sampling = task.search_constraint.sampling;
+        self.canonical_cache = task.canonical_cache;
+        self.stats = stats::SearchStats::default();
+
+        let start = Instant::now();
+        let mut search = stack::Search::new(task.state, self.depth_limit);
+        let result = search.resume(self);
+
+        self.stats.nodes = search.nodes();
+        self.stats.iterations.push(stats::IterationStats {
+            depth: self.depth_limit,
+            duration: start.elapsed(),
+            nodes: search.nodes(),
+            completed: result.is_ok(),
+        });
+        if result.is_err() {
+            log::trace!(
+                "Timed out after evaluating {} root actions",
+                search.evaluated_actions().len()
+            );
+        }
 
         super::SearchResult {
-            result: self.make_decision(&task.state),
+            result,
+            partial: search.partial_decision(),
+            evaluated_actions: search.evaluated_actions().to_vec(),
+            stats: std::mem::take(&mut self.stats),
             task_id: task.task_id,
-            state: task.state,
         }
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::cache::CacheKind;
+    use super::cache::SharedCache;
+    use super::stack::Search;
+    use super::{Decision, EvaluationCache, MaxDepth, Sampling, SearchError, Searcher};
+    use crate::bots::heuristic::TwentyFortyEightHeuristic;
+    use crate::bots::mean_max::logger::{Logger, LoggerHandle, LoggerSettings};
+    use crate::game::twenty_forty_eight::State;
+    use std::sync::{Arc, Mutex};
+    use std::time::{Duration, Instant};
+
+    fn searcher() -> Searcher<State<4, 4>, TwentyFortyEightHeuristic<4, 4>> {
+        searcher_with_cache(Arc::new(SharedCache::new(0x10000)))
+    }
+
+    fn searcher_with_cache(
+        cache: Arc<EvaluationCache<State<4, 4>>>,
+    ) -> Searcher<State<4, 4>, TwentyFortyEightHeuristic<4, 4>> {
+        let logger = LoggerHandle::new(Arc::new(Mutex::new(Logger::with_settings(
+            LoggerSettings::default(),
+        ))));
+        Searcher::new(TwentyFortyEightHeuristic::new(), cache, logger)
+    }
+
+    fn decide(
+        searcher: &mut Searcher<State<4, 4>, TwentyFortyEightHeuristic<4, 4>>,
+        state: &State<4, 4>,
+        depth: u8,
+    ) -> Decision<super::twenty_forty_eight::board::Direction> {
+        searcher.depth_limit = MaxDepth::new(depth);
+        Search::new(state.clone(), searcher.depth_limit)
+            .resume(searcher)
+            .unwrap()
+    }
+
+    #[test]
+    fn test_resume() {
+        let state = State::from_cells([[3, 3, 1, 1], [1, 0, 5, 0], [0, 2, 7, 4], [6, 1, 6, 8]]);
+        let expected = decide(&mut searcher(), &state, 3);
+
+        let mut searcher = searcher();
+        let mut search = Search::new(state, MaxDepth::new(3));
+        let mut evaluated_actions = 0;
+        let mut suspensions = 0;
+
+        let decision = loop {
+            searcher.deadline = Some(Instant::now() + Duration::from_micros(100));
+            match search.resume(&mut searcher) {
+                Ok(decision) => break decision,
+                Err(SearchError::TimeOut) => suspensions += 1,
+                Err(err) => panic!("unexpected error: {err}"),
+            }
+
+            // Root actions that were fully evaluated are kept across suspensions.
+            assert!(search.evaluated_actions().len() >= evaluated_actions);
+            evaluated_actions = search.evaluated_actions().len();
+        };
+
+        assert!(suspensions > 0);
+        assert_eq!(decision, expected);
+        assert_eq!(search.partial_decision(), expected);
+    }
+
+    #[test]
+    fn test_node_budget() {
+        let state = State::from_cells([[3, 3, 1, 1], [1, 0, 5, 0], [0, 2, 7, 4], [6, 1, 6, 8]]);
+        let expected = decide(&mut searcher(), &state, 3);
+        assert_eq!(expected.nodes(), decide(&mut searcher(), &state, 3).nodes());
+
+        let mut searcher = searcher();
+        let mut search = Search::new(state, MaxDepth::new(3));
+        searcher.max_nodes = Some(expected.nodes() / 2);
+        assert!(matches!(
+            search.resume(&mut searcher),
+            Err(SearchError::NodeBudget)
+        ));
+        assert_eq!(search.nodes(), expected.nodes() / 2);
+
+        // Raising the budget resumes the search.
+        searcher.max_nodes = Some(expected.nodes());
+        assert_eq!(search.resume(&mut searcher).unwrap(), expected);
+    }
+
+    #[test]
+    fn test_canonical_cache_size() {
+        let state = State::from_cells([[1, 0, 0, 0], [0, 0, 0, 0], [0, 0, 0, 0], [0, 0, 0, 1]]);
+        let mut plain = searcher();
+        let mut canonical = searcher();
+        canonical.canonical_cache = true;
+
+        decide(&mut plain, &state, 3);
+        decide(&mut canonical, &state, 3);
+
+        // Most of the positions reachable from a symmetric board have a symmetric twin.
+        assert!(2 * canonical.evaluation_cache.len() < plain.evaluation_cache.len());
+    }
+
+    #[test]
+    fn test_shared_cache() {
+        let state = State::from_cells([[1, 0, 0, 0], [0, 2, 0, 0], [0, 0, 0, 0], [0, 0, 0, 1]]);
+        let mut deep = searcher();
+        let mut shallow = searcher();
+        shallow.evaluation_cache = Arc::clone(&deep.evaluation_cache);
+
+        let expected = decide(&mut deep, &state, 3);
+        let cache_size = deep.evaluation_cache.len();
+
+        // The deeper evaluations of the other searcher answer the whole shallow search.
+        let decision = decide(&mut shallow, &state, 2);
+        assert_eq!(decision.eval(), expected.eval());
+        assert_eq!(decision.nodes(), 1);
+        assert_eq!(shallow.evaluation_cache.len(), cache_size);
+    }
+
+    #[test]
+    fn test_probability_cutoff() {
+        let state = State::from_cells([[1, 0, 0, 0], [0, 2, 0, 0], [0, 0, 0, 0], [0, 0, 0, 1]]);
+        let mut plain = searcher();
+        let mut cutoff = searcher();
+        cutoff.min_probability = Some(0.01);
+
+        let expected = decide(&mut plain, &state, 3);
+        let decision = decide(&mut cutoff, &state, 3);
+        assert!(decision.eval().truncated);
+        // The states that are cut off don't limit the depth.
+        assert!(decision.eval().min_depth >= expected.eval().min_depth);
+        assert!(cutoff.evaluation_cache.len() < plain.evaluation_cache.len());
+
+        // The evaluations truncated by a looser cutoff are searched again.
+        let mut strict = searcher();
+        strict.min_probability = Some(0.001);
+        let expected_strict = decide(&mut strict, &state, 3);
+        cutoff.min_probability = Some(0.001);
+        let decision = decide(&mut cutoff, &state, 3);
+        assert_eq!(decision.eval(), expected_strict.eval());
+
+        // The truncated evaluations are searched again without a cutoff.
+        cutoff.min_probability = None;
+        let decision = decide(&mut cutoff, &state, 3);
+        assert!(!decision.eval().truncated);
+        assert_eq!(decision.eval().min_depth, expected.eval().min_depth);
+    }
+
+    #[test]
+    fn test_exact_replaces_approximate() {
+        let state = State::from_cells([[1, 0, 0, 0], [0, 2, 0, 0], [0, 0, 0, 0], [0, 0, 0, 1]]);
+
+        for kind in [CacheKind::Priority, CacheKind::Transposition] {
+            let mut searcher = searcher_with_cache(kind.with_byte_budget(1 << 22));
+            searcher.min_probability = Some(0.01);
+            decide(&mut searcher, &state, 4);
+
+            // The deeper approximate evaluations don't keep the exact ones out of the cache.
+            searcher.min_probability = None;
+            let expected = decide(&mut searcher, &state, 2);
+            searcher.stats = Default::default();
+            let decision = decide(&mut searcher, &state, 2);
+
+            assert_eq!(decision.eval(), expected.eval(), "{kind:?}");
+            assert_eq!(searcher.stats.heuristic_calls, 0, "{kind:?}");
+            assert!(searcher.stats.total_cache_hits() > 0, "{kind:?}");
+        }
+    }
+
+    #[test]
+    fn test_sampling() {
+        let state = State::from_cells([[1, 0, 0, 0], [0, 2, 0, 0], [0, 0, 0, 0], [0, 0, 0, 1]]);
+        let mut plain = searcher();
+        let mut sampling = searcher();
+        sampling.sampling = Some(Sampling::new(4).with_samples_from(1, 2));
+
+        let expected = decide(&mut plain, &state, 3);
+        assert_eq!(expected.eval().variance, 0.0);
+
+        let decision = decide(&mut sampling, &state, 3);
+        assert!(decision.eval().sampled);
+        assert!(decision.eval().variance > 0.0);
+        assert_eq!(decision.eval().min_depth, expected.eval().min_depth);
+        assert_eq!(decision.eval().samples, 2);
+        assert!(sampling.evaluation_cache.len() < plain.evaluation_cache.len());
+
+        // The same sample is drawn by another searcher.
+        let mut other = searcher();
+        other.sampling = sampling.sampling;
+        assert_eq!(decide(&mut other, &state, 3), decision);
+
+        // Evaluations from fewer samples are searched again.
+        sampling.sampling = Some(Sampling::new(4));
+        let decision = decide(&mut sampling, &state, 3);
+        assert_eq!(decision.eval().samples, 4);
+
+        // The sampled evaluations are searched again without sampling.
+        sampling.sampling = None;
+        let decision = decide(&mut sampling, &state, 3);
+        assert!(!decision.eval().sampled);
+        assert_eq!(decision.eval().variance, 0.0);
+    }
+
+    #[test]
+    fn test_canonical_cache() {
+        let states = [
+            State::from_cells([[3, 3, 1, 1], [1, 0, 5, 0], [0, 2, 7, 4], [6, 1, 6, 8]]),
+            State::from_cells([[0, 0, 0, 0], [0, 0, 0, 0], [0, 1, 0, 0], [0, 0, 0, 1]]),
+        ];
+
+        for state in states {
+            // NOTE: Deeper searches can reach the same position at different depths, and use the
+            // deeper cached evaluation. So only these depths give the exact same values.
+            for depth in 0..2 {
+                let mut plain = searcher();
+                let mut canonical = searcher();
+                canonical.canonical_cache = true;
+
+                let expected = decide(&mut plain, &state, depth);
+                let decision = decide(&mut canonical, &state, depth);
+
+                let (Decision::Act(expected), Decision::Act(decision)) = (expected, decision)
+                else {
+                    panic!("Expected both searches to act on:\n{state}");
+                };
+
+                // NOTE: Actions are not compared since symmetric actions may tie.
+                assert_eq!(decision.eval.min_depth, expected.eval.min_depth);
+                let error = (decision.eval.value - expected.eval.value).abs();
+                assert!(
+                    error <= expected.eval.value * 1e-4,
+                    "{decision} != {expected}"
+                );
+                assert!(canonical.evaluation_cache.len() <= plain.evaluation_cache.len());
+            }
+        }
+    }
+
+    #[test]
+    fn test_transposition_table() {
+        let state = State::from_cells([[3, 3, 1, 1], [1, 0, 5, 0], [0, 2, 7, 4], [6, 1, 6, 8]]);
+        let expected = decide(&mut searcher(), &state, 3);
+
+        let mut table = searcher_with_cache(CacheKind::Transposition.with_byte_budget(1 << 20));
+        assert_eq!(decide(&mut table, &state, 3), expected);
+        assert!(!table.evaluation_cache.is_empty());
+    }
+}
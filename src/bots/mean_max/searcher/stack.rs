@@ -0,0 +1,497 @@
+//! Expectimax on an explicit stack instead of the native one.
+//!
+//! Every level of the search tree has one frame on the stack, holding the iterator over the
+//! remaining actions or spawns of the node. So the memory of a search only grows with its depth,
+//! and a search that hits the deadline can be resumed where it stopped.
+
+use super::{Decision, EvaluatedAction, Evaluation, SearchError, Searcher, Value};
+use crate::accumulator::fraction::{Weighted, WeightedAverage};
+use crate::bots::heuristic;
+use crate::bots::mean_max::max_depth::MaxDepth;
+use crate::game::{self, DiscreteDistribution};
+use rand::distr::{weighted::WeightedIndex, Distribution as _};
+use rand::{rngs::StdRng, SeedableRng as _};
+use std::fmt::{Debug, Display};
+use std::hash::{DefaultHasher, Hash, Hasher as _};
+
+/// A max node, picking the best action of the state.
+struct DecisionFrame<G: game::GameState> {
+    state: G,
+    depth_limit: MaxDepth,
+    /// Probability of reaching the state from the root of the search.
+    probability: Value,
+    actions: std::vec::IntoIter<G::Action>,
+    /// Action whose outcome is being evaluated, with its reward.
+    current: Option<(G::Action, G::Reward)>,
+    best: Decision<G::Action>,
+}
+
+/// A chance node, averaging the evaluations of the states the outcome can collapse to.
+pub(super) struct OutcomeFrame<G: game::GameState>
+where
+    G::Outcome: DiscreteDistribution<T = G>,
+{
+    pub(super) outcome: G::Outcome,
+    pub(super) cache_key: G::Outcome,
+    /// Depth limit of the states of the outcome.
+    depth_limit: MaxDepth,
+    /// Probability of reaching the outcome from the root of the search.
+    probability: Value,
+    /// Sum of the weights of the states, only computed with a probability cutoff or sampling.
+    total_weight: Value,
+    states: States<G>,
+    /// Number of states drawn if the outcome is sampled.
+    sample_count: Option<usize>,
+    /// Next state to evaluate, once the deadline is checked.
+    pending: Option<(G, Value)>,
+    /// Weight of the state being evaluated.
+    weight: Value,
+    pub(super) mean_value: WeightedAverage<Value, Value>,
+    pub(super) min_depth: MaxDepth,
+    pub(super) truncated: bool,
+    pub(super) sampled: bool,
+    /// See [`Evaluation::samples`].
+    pub(super) samples: u16,
+    /// Weighted sum of the squared evaluations of the states.
+    squares: Value,
+    /// Sum of the variances of the states, weighted by their squared weight.
+    variances: Value,
+}
+
+/// States of an outcome that are left to search, with their weight.
+enum States<G: game::GameState>
+where
+    G::Outcome: DiscreteDistribution<T = G>,
+{
+    All(<G::Outcome as IntoIterator>::IntoIter),
+    /// Distinct sampled states, weighted by the number of times they were drawn.
+    Sampled(std::vec::IntoIter<(G, Value)>),
+}
+
+impl<G: game::GameState> Iterator for States<G>
+where
+    G::Outcome: DiscreteDistribution<T = G>,
+    Value: From<<G::Outcome as DiscreteDistribution>::Weight>,
+{
+    type Item = (G, Value);
+
+    fn next(&mut self) -> Option<Self::Item> {
+        match self {
+            States::All(states) => states
+                .next()
+                .map(|weighted| (weighted.value, Value::from(weighted.weight))),
+            States::Sampled(states) => states.next(),
+        }
+    }
+}
+
+impl<G: game::GameState> OutcomeFrame<G>
+where
+    G::Outcome: DiscreteDistribution<T = G>,
+{
+    pub(super) fn probability(&self) -> Value {
+        self.probability
+    }
+
+    /// Variance of the mean of the evaluations of the states.
+    ///
+    /// For a sampled outcome it's estimated from the spread of the samples, which already
+    /// includes the variance of their own evaluations.
+    pub(super) fn variance(&self) -> Value {
+        let weight = *self.mean_value.weight();
+        let mean = self.mean_value.clone().evaluate();
+
+        match self.sample_count {
+            Some(count) if count > 1 => {
+                let count = count as Value;
+                let sample_variance = (self.squares - weight * mean * mean) / (count - 1.0);
+                sample_variance.max(0.0) / count
+            }
+            Some(_) => self.variances,
+            None => self.variances / (weight * weight),
+        }
+    }
+}
+
+enum Frame<G: game::GameState>
+where
+    G::Outcome: DiscreteDistribution<T = G>,
+{
+    Decision(DecisionFrame<G>),
+    Outcome(OutcomeFrame<G>),
+}
+
+/// Result of starting the evaluation of an outcome.
+enum OutcomeStart<G: game::GameState>
+where
+    G::Outcome: DiscreteDistribution<T = G>,
+{
+    /// The evaluation is known without searching the states of the outcome.
+    Evaluated(Evaluation),
+    Search(OutcomeFrame<G>),
+}
+
+/// A search from a root state that can be suspended when the deadline is hit and resumed later.
+pub(in crate::bots::mean_max) struct Search<G: game::GameState>
+where
+    G::Outcome: DiscreteDistribution<T = G>,
+{
+    frames: Vec<Frame<G>>,
+    /// Evaluation returned by the frame that was just popped to its parent.
+    returned: Option<Evaluation>,
+    /// Root actions whose outcome has been fully evaluated.
+    evaluated_actions: Vec<EvaluatedAction<G::Action>>,
+    decision: Option<Decision<G::Action>>,
+    /// Number of decision nodes expanded so far.
+    nodes: u64,
+    /// Value of `nodes` when the current root action started to be evaluated.
+    root_action_start: u64,
+}
+
+impl<G> Search<G>
+where
+    G: game::GameState + Clone + Display,
+    G::Outcome: DiscreteDistribution<T = G> + game::Canonical + Hash + Ord + Clone + Display,
+    G::Action: game::Discrete + Clone + Display,
+    Value: From<G::Reward> + From<<G::Outcome as DiscreteDistribution>::Weight>,
+    <G::Outcome as DiscreteDistribution>::Weight: Debug,
+{
+    pub fn new(state: G, depth_limit: MaxDepth) -> Self {
+        let root = DecisionFrame::new(state, depth_limit, 1.0);
+
+        Self {
+            frames: vec![Frame::Decision(root)],
+            returned: None,
+            evaluated_actions: Vec::new(),
+            decision: None,
+            nodes: 1,
+            root_action_start: 1,
+        }
+    }
+
+    /// Number of decision nodes expanded so far.
+    pub fn nodes(&self) -> u64 {
+        self.nodes
+    }
+
+    /// Root actions whose outcome has been fully evaluated so far.
+    pub fn evaluated_actions(&self) -> &[EvaluatedAction<G::Action>] {
+        &self.evaluated_actions
+    }
+
+    /// Best of the root actions evaluated so far, [`Decision::Resign`] if there are none.
+    pub fn partial_decision(&self) -> Decision<G::Action> {
+        self.evaluated_actions
+            .iter()
+            .cloned()
+            .map(Decision::Act)
+            .fold(Decision::Resign, Decision::max_by_eval)
+            .with_nodes(self.nodes)
+    }
+
+    /// Searches until the tree is fully evaluated or a limit of the searcher is hit.
+    ///
+    /// # Errors
+    ///
+    /// Returns [`SearchError::TimeOut`] when the deadline is hit, calling this again continues the
+    /// search from where it stopped. Same for [`SearchError::NodeBudget`] once the budget of the
+    /// searcher is raised, and [`SearchError::Cancelled`].
+    pub fn resume<H>(
+        &mut self,
+        searcher: &mut Searcher<G, H>,
+    ) -> Result<Decision<G::Action>, SearchError>
+    where
+        H: heuristic::Heuristic<G::Outcome, Value>,
+        G::Outcome: 'static,
+    {
+        loop {
+            let is_root = self.frames.len() == 1;
+            // Number of outcomes between the root and the top frame.
+            let depth = self.frames.len() / 2;
+            let Some(frame) = self.frames.last_mut() else {
+                return Ok(self
+                    .decision
+                    .clone()
+                    .expect("the decision is set when the root frame is popped"));
+            };
+
+            match frame {
+                Frame::Decision(frame) => {
+                    if let Some(eval) = self.returned.take() {
+                        let mut evaluated_action = frame.evaluated(eval);
+                        if is_root {
+                            evaluated_action.nodes = self.nodes - self.root_action_start;
+                            self.evaluated_actions.push(evaluated_action.clone());
+                            self.root_action_start = self.nodes;
+                        }
+
+                        frame.best = frame
+                            .best
+                            .clone()
+                            .max_by_eval(Decision::Act(evaluated_action));
+                    }
+
+                    let Some(action) = frame.actions.next() else {
+                        let decision = frame.best.clone();
+                        self.frames.pop();
+                        self.returned = Some(decision.eval());
+                        if self.frames.is_empty() {
+                            self.decision = Some(decision.with_nodes(self.nodes));
+                        }
+
+                        continue;
+                    };
+
+                    let Ok((reward, outcome)) = frame.state.clone().outcome(action.clone()) else {
+                        unreachable!("legal action {action} should be applicable");
+                    };
+
+                    let (depth_limit, probability) = (frame.depth_limit, frame.probability);
+                    frame.current = Some((action, reward));
+
+                    match searcher.start_outcome(outcome, depth_limit, probability, depth) {
+                        OutcomeStart::Evaluated(eval) => self.returned = Some(eval),
+                        OutcomeStart::Search(frame) => self.frames.push(Frame::Outcome(frame)),
+                    }
+                }
+
+                Frame::Outcome(frame) => {
+                    if let Some(eval) = self.returned.take() {
+                        frame.min_depth = std::cmp::min(eval.min_depth, frame.min_depth);
+                        frame.truncated |= eval.truncated;
+                        frame.sampled |= eval.sampled;
+                        frame.samples = frame.samples.min(eval.samples);
+                        frame.squares += frame.weight * eval.value * eval.value;
+                        frame.variances += frame.weight * frame.weight * eval.variance;
+                        frame.mean_value += Weighted {
+                            value: eval.value,
+                            weight: frame.weight,
+                        };
+                    }
+
+                    if frame.pending.is_none() {
+                        frame.pending = frame.states.next();
+                    }
+
+                    if frame.pending.is_none() {
+                        let Some(Frame::Outcome(frame)) = self.frames.pop() else {
+                            unreachable!("the top frame is an outcome frame");
+                        };
+
+                        self.returned = Some(searcher.finish_outcome(frame));
+                        continue;
+                    }
+
+                    if searcher.is_past_deadline() {
+                        return Err(SearchError::TimeOut);
+                    }
+
+                    if searcher.is_cancelled() {
+                        return Err(SearchError::Cancelled);
+                    }
+
+                    if searcher
+                        .max_nodes
+                        .is_some_and(|max_nodes| self.nodes >= max_nodes)
+                    {
+                        return Err(SearchError::NodeBudget);
+                    }
+
+                    let (state, weight) = frame.pending.take().expect("checked above");
+                    frame.weight = weight;
+
+                    let probability = frame.probability * weight / frame.total_weight;
+                    let child = DecisionFrame::new(state, frame.depth_limit, probability);
+                    self.nodes += 1;
+                    self.frames.push(Frame::Decision(child));
+                }
+            }
+        }
+    }
+}
+
+impl<G> DecisionFrame<G>
+where
+    G: game::GameState,
+    G::Action: game::Discrete + Clone,
+    Value: From<G::Reward>,
+{
+    fn new(state: G, depth_limit: MaxDepth, probability: Value) -> Self {
+        let actions: Vec<_> = state.legal_actions().collect();
+
+        Self {
+            state,
+            depth_limit,
+            probability,
+            actions: actions.into_iter(),
+            current: None,
+            best: Decision::Resign,
+        }
+    }
+
+    /// Adds the reward of the current action to the evaluation of its outcome.
+    fn evaluated(&mut self, eval: Evaluation) -> EvaluatedAction<G::Action> {
+        let (action, reward) = self
+            .current
+            .take()
+            .expect("an evaluation is only returned for the current action");
+
+        let eval = Evaluation {
+            value: eval.value + Value::from(reward),
+            ..eval
+        };
+
+        EvaluatedAction {
+            eval,
+            action,
+            nodes: 0,
+        }
+    }
+}
+
+impl<G, H> Searcher<G, H>
+where
+    G: game::GameState + Clone + Display,
+    G::Outcome: DiscreteDistribution<T = G> + game::Canonical + Hash + Ord + Clone + Display,
+    G::Action: game::Discrete + Clone + Display,
+    Value: From<G::Reward> + From<<G::Outcome as DiscreteDistribution>::Weight>,
+    H: heuristic::Heuristic<G::Outcome, Value>,
+    <G::Outcome as DiscreteDistribution>::Weight: Debug,
+{
+    /// Evaluates the outcome from the cache or the heuristic if possible, otherwise returns the
+    /// frame to search its states.
+    fn start_outcome(
+        &mut self,
+        outcome: G::Outcome,
+        depth_limit: MaxDepth,
+        probability: Value,
+        depth: usize,
+    ) -> OutcomeStart<G> {
+        debug_assert!(
+            outcome.clone().into_iter().next().is_some(),
+            "the outcome of a legal action has at least one state"
+        );
+
+        // Symmetric outcomes have the same evaluation, so they can share a cache entry.
+        let cache_key = match self.canonical_cache {
+            true => game::Canonical::canonical(&outcome),
+            false => outcome.clone(),
+        };
+
+        if let Some(evaluation) =
+            self.cached_evaluation(&cache_key, depth_limit, probability, depth)
+        {
+            return OutcomeStart::Evaluated(evaluation);
+        }
+
+        let Some(depth_limit) = depth_limit - 1 else {
+            return OutcomeStart::Evaluated(Evaluation {
+                value: self.heuristic_eval(&outcome),
+                min_depth: MaxDepth::new(0),
+                truncated: false,
+                cutoff: 0.0,
+                sampled: false,
+                samples: u16::MAX,
+                variance: 0.0,
+            });
+        };
+
+        let mut total_weight = match self.min_probability {
+            None => 1.0,
+            Some(min_probability) if probability < min_probability => {
+                // Too unlikely to be worth searching, at any depth. So the evaluation doesn't
+                // depend on the depth, only on the cutoff of the search.
+                return OutcomeStart::Evaluated(Evaluation {
+                    value: self.heuristic_eval(&outcome),
+                    min_depth: MaxDepth::Unlimited,
+                    truncated: true,
+                    cutoff: min_probability / probability,
+                    sampled: false,
+                    samples: u16::MAX,
+                    variance: 0.0,
+                });
+            }
+            Some(_) => outcome
+                .clone()
+                .into_iter()
+                .map(|weighted| Value::from(weighted.weight))
+                .sum(),
+        };
+
+        let sampled_states = self.sampling.and_then(|sampling| {
+            let count = sampling.samples_at(depth);
+            let states = sample_states::<G>(&outcome, count, sampling.seed())?;
+            Some((states, count))
+        });
+        let (states, sample_count) = match sampled_states {
+            Some((states, count)) => {
+                total_weight = states.iter().map(|(_, weight)| weight).sum();
+                (States::Sampled(states.into_iter()), Some(count))
+            }
+            None => (States::All(outcome.clone().into_iter()), None),
+        };
+
+        OutcomeStart::Search(OutcomeFrame {
+            states,
+            sample_count,
+            outcome,
+            cache_key,
+            depth_limit,
+            probability,
+            total_weight,
+            pending: None,
+            weight: 0.0,
+            mean_value: WeightedAverage::default(),
+            min_depth: MaxDepth::Unlimited,
+            truncated: false,
+            sampled: sample_count.is_some(),
+            samples: sample_count.map_or(u16::MAX, |count| count as u16),
+            squares: 0.0,
+            variances: 0.0,
+        })
+    }
+
+    fn heuristic_eval(&mut self, outcome: &G::Outcome) -> Value {
+        self.stats.heuristic_calls += 1;
+        self.heuristic.eval(outcome)
+    }
+}
+
+/// Draws `count` states of the outcome according to their weight, returns `None` if the
+/// outcome has too few states to be worth sampling. The draws only depend on the seed and the
+/// outcome.
+pub(crate) fn sample_states<G>(
+    outcome: &G::Outcome,
+    count: usize,
+    seed: u64,
+) -> Option<Vec<(G, Value)>>
+where
+    G: game::GameState,
+    G::Outcome: DiscreteDistribution<T = G> + Hash + Clone,
+    Value: From<<G::Outcome as DiscreteDistribution>::Weight>,
+{
+    let states: Vec<_> = States::<G>::All(outcome.clone().into_iter()).collect();
+    if states.len() <= count {
+        return None;
+    }
+
+    let distribution = WeightedIndex::new(states.iter().map(|&(_, weight)| weight)).ok()?;
+
+    let mut hasher = DefaultHasher::new();
+    (seed, outcome).hash(&mut hasher);
+    let mut rng = StdRng::seed_from_u64(hasher.finish());
+
+    let mut draws = vec![0_u32; states.len()];
+    for _ in 0..count {
+        draws[distribution.sample(&mut rng)] += 1;
+    }
+
+    let sampled = states
+        .into_iter()
+        .zip(draws)
+        .filter(|&(_, draws)| draws > 0)
+        .map(|((state, _weight), draws)| (state, draws as Value))
+        .collect();
+
+    Some(sampled)
+}
@@ -0,0 +1,131 @@
+use crate::accumulator::Accumulator;
+use crate::bots::mean_max::max_depth::MaxDepth;
+use crate::utils;
+use std::fmt::Display;
+use std::time::Duration;
+
+/// Counters of a search, gathered by every searcher and merged.
+#[derive(Clone, Debug, Default)]
+pub struct SearchStats {
+    /// Number of decision nodes expanded.
+    pub nodes: u64,
+    /// Cache lookups that found a deep enough evaluation, by depth limit of the lookup.
+    pub cache_hits: Accumulator<MaxDepth, u64>,
+    /// Cache lookups that found no evaluation or a too shallow one, by depth limit of the lookup.
+    pub cache_misses: Accumulator<MaxDepth, u64>,
+    pub heuristic_calls: u64,
+    /// Every search of the iterative deepening, in the order they ended.
+    pub iterations: Vec<IterationStats>,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct IterationStats {
+    pub depth: MaxDepth,
+    pub duration: Duration,
+    pub nodes: u64,
+    /// The search was not stopped by a deadline, node budget or cancellation.
+    pub completed: bool,
+}
+
+impl SearchStats {
+    /// Depth of the deepest search that was completed.
+    pub fn deepest_iteration(&self) -> Option<MaxDepth> {
+        self.iterations
+            .iter()
+            .filter(|iteration| iteration.completed)
+            .map(|iteration| iteration.depth)
+            .max()
+    }
+
+    pub fn total_cache_hits(&self) -> u64 {
+        self.cache_hits.memory.values().sum()
+    }
+
+    pub fn total_cache_misses(&self) -> u64 {
+        self.cache_misses.memory.values().sum()
+    }
+
+    pub fn merge(&mut self, other: SearchStats) {
+        self.nodes += other.nodes;
+        self.heuristic_calls += other.heuristic_calls;
+
+        for (depth, hits) in other.cache_hits.memory {
+            self.cache_hits.accumulate(depth, hits);
+        }
+
+        for (depth, misses) in other.cache_misses.memory {
+            self.cache_misses.accumulate(depth, misses);
+        }
+
+        self.iterations.extend(other.iterations);
+    }
+}
+
+impl Display for SearchStats {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        writeln!(f, "Nodes: {}", self.nodes)?;
+        writeln!(f, "Heuristic calls: {}", self.heuristic_calls)?;
+        writeln!(
+            f,
+            "Cache hits: {}, misses: {}",
+            self.total_cache_hits(),
+            self.total_cache_misses()
+        )?;
+
+        for iteration in &self.iterations {
+            let duration = utils::HumanDuration(iteration.duration);
+            let status = if iteration.completed {
+                ""
+            } else {
+                " (stopped)"
+            };
+            writeln!(
+                f,
+                "Depth {:>2}: {} nodes in {duration:>5}{status}",
+                iteration.depth, iteration.nodes
+            )?;
+        }
+
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{IterationStats, SearchStats};
+    use crate::bots::mean_max::max_depth::MaxDepth;
+    use std::time::Duration;
+
+    #[test]
+    fn test_merge() {
+        let iteration = |depth, completed| IterationStats {
+            depth: MaxDepth::new(depth),
+            duration: Duration::from_millis(1),
+            nodes: 10,
+            completed,
+        };
+
+        let mut stats = SearchStats {
+            nodes: 10,
+            iterations: vec![iteration(2, true)],
+            ..SearchStats::default()
+        };
+        stats.cache_hits.accumulate(MaxDepth::new(1), 3);
+
+        let mut other = SearchStats {
+            nodes: 20,
+            heuristic_calls: 5,
+            iterations: vec![iteration(3, true), iteration(4, false)],
+            ..SearchStats::default()
+        };
+        other.cache_hits.accumulate(MaxDepth::new(1), 2);
+        other.cache_misses.accumulate(MaxDepth::new(2), 1);
+
+        stats.merge(other);
+        assert_eq!(stats.nodes, 30);
+        assert_eq!(stats.heuristic_calls, 5);
+        assert_eq!(stats.cache_hits.memory[&MaxDepth::new(1)], 5);
+        assert_eq!(stats.total_cache_misses(), 1);
+        assert_eq!(stats.deepest_iteration(), Some(MaxDepth::new(3)));
+    }
+}
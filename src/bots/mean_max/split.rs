@@ -0,0 +1,326 @@
+//! Split-work search, where the searcher threads share every iteration of the search.
+//!
+//! The outcomes of the root actions are expanded here, and every state drawn from them is a work
+//! item searched one level shallower by the next idle thread. The evaluations of the states are
+//! merged into the evaluations of the root actions, like the search merges an outcome.
+
+use super::deepening::Deepening;
+use super::max_depth::MaxDepth;
+use super::searcher::stack;
+use super::searcher::stats::{IterationStats, SearchStats};
+use super::searcher::{
+    self, CancellationToken, Decision, EvaluatedAction, Evaluation, SearchConstraint, SearchError,
+    Value,
+};
+use super::{MeanMax, SearchResult, Task};
+use crate::accumulator::fraction::{Weighted, WeightedAverage};
+use crate::game;
+use std::collections::{HashMap, VecDeque};
+use std::fmt::{Debug, Display};
+use std::hash::Hash;
+use std::time::Instant;
+
+/// A state after a root action, searched by one thread.
+struct WorkItem<G> {
+    /// Index of the root action in the [`ActionWork`]s of the iteration.
+    action: usize,
+    state: G,
+    /// Probability of the state after the root action.
+    probability: Value,
+}
+
+/// Evaluation of a root action, merged from the evaluations of the states after it.
+struct ActionWork<A> {
+    action: A,
+    reward: Value,
+    mean_value: WeightedAverage<Value, Value>,
+    min_depth: MaxDepth,
+    truncated: bool,
+    /// Cutoff of the states relative to the outcome of the action.
+    cutoff: Value,
+    sampled: bool,
+    samples: u16,
+    /// Number of states drawn if the outcome of the action is sampled.
+    sample_count: Option<usize>,
+    /// Sum of the squared evaluations of the states, weighted by their probability.
+    squares: Value,
+    /// Sum of the variances of the states, weighted by their squared probability.
+    variances: Value,
+    nodes: u64,
+    /// Number of states that are not evaluated yet.
+    pending: usize,
+}
+
+impl<A> ActionWork<A> {
+    fn add(&mut self, probability: Value, eval: Evaluation, nodes: u64) {
+        self.mean_value += Weighted::new_weighted(eval.value, probability);
+        self.min_depth = std::cmp::min(self.min_depth, eval.min_depth);
+        self.truncated |= eval.truncated;
+        self.cutoff = self.cutoff.max(eval.cutoff * probability);
+        self.sampled |= eval.sampled;
+        self.samples = self.samples.min(eval.samples);
+        self.squares += probability * eval.value * eval.value;
+        self.variances += probability * probability * eval.variance;
+        self.nodes += nodes;
+        self.pending -= 1;
+    }
+
+    fn evaluated(self) -> EvaluatedAction<A> {
+        let weight = *self.mean_value.weight();
+        // An outcome without states ends the game.
+        let (mean, variance) = match weight > 0.0 {
+            true => {
+                let mean = self.mean_value.evaluate();
+                // Estimated from the spread of the samples, like the search does.
+                let variance = match self.sample_count {
+                    Some(count) if count > 1 => {
+                        let sample_variance = self.squares / weight - mean * mean;
+                        sample_variance.max(0.0) / (count as Value - 1.0)
+                    }
+                    _ => self.variances / (weight * weight),
+                };
+                (mean, variance)
+            }
+            false => (0.0, 0.0),
+        };
+
+        EvaluatedAction {
+            eval: Evaluation {
+                value: self.reward + mean,
+                min_depth: self.min_depth + 1,
+                truncated: self.truncated,
+                cutoff: self.cutoff,
+                sampled: self.sampled,
+                samples: self.samples,
+                variance,
+            },
+            action: self.action,
+            nodes: self.nodes,
+        }
+    }
+}
+
+impl<G, H> MeanMax<G, H>
+where
+    G: game::GameState + Send + Clone + Display + 'static,
+    G::Outcome:
+        game::DiscreteDistribution<T = G> + game::Canonical + Hash + Ord + Clone + Display + Send,
+    G::Action: game::Discrete + Send + Clone + Display + PartialEq,
+    searcher::Value: From<G::Reward> + From<<G::Outcome as game::DiscreteDistribution>::Weight>,
+    H: crate::bots::heuristic::Heuristic<G::Outcome, searcher::Value> + 'static,
+    <G::Outcome as game::DiscreteDistribution>::Weight: Debug,
+{
+    /// Searches deeper and deeper, every iteration split between the searchers.
+    pub(super) fn search_split(&mut self, state: &G, deepening: &mut Deepening<G::Action>) {
+        while let Some(depth) = deepening.next_depth() {
+            let (search_constraint, cancellation) = deepening.iteration(depth);
+
+            let start = Instant::now();
+            let nodes = deepening.stats.nodes;
+            let searchers = match deepening.is_sequential() {
+                true => 1,
+                false => self.searcher_threads.len(),
+            };
+            let (result, evaluated_actions) = self.split_iteration(
+                state,
+                search_constraint,
+                cancellation,
+                searchers,
+                &mut deepening.stats,
+            );
+            let duration = start.elapsed();
+
+            deepening.stats.iterations.push(IterationStats {
+                depth,
+                duration,
+                nodes: deepening.stats.nodes - nodes,
+                completed: result.is_ok(),
+            });
+
+            let decision = partial_decision(&evaluated_actions);
+            match result {
+                Ok(()) => deepening.complete(depth, duration, decision, evaluated_actions),
+                Err(err) => deepening.stop(err, decision, evaluated_actions),
+            }
+        }
+    }
+
+    /// Searches the root actions to the depth of `search_constraint`, by handing out the states
+    /// after them to the first `searchers` searchers.
+    ///
+    /// Returns the root actions that were fully evaluated, all of them unless the iteration was
+    /// stopped.
+    fn split_iteration(
+        &mut self,
+        state: &G,
+        search_constraint: SearchConstraint,
+        cancellation: Option<CancellationToken>,
+        searchers: usize,
+        stats: &mut SearchStats,
+    ) -> (Result<(), SearchError>, Vec<EvaluatedAction<G::Action>>) {
+        let Some(item_depth) = search_constraint.max_depth - 1 else {
+            // The outcomes of the root actions are evaluated by the heuristic of a searcher.
+            let task = Task {
+                task_id: 0,
+                state: state.clone(),
+                search_constraint,
+                canonical_cache: self.canonical_cache,
+                cancellation,
+            };
+            self.send_task(task);
+
+            let search = self.receive_merged(stats);
+            return (search.result.map(|_| ()), search.evaluated_actions);
+        };
+
+        let mut actions = Vec::new();
+        let mut items = VecDeque::new();
+        for action in state.legal_actions() {
+            let Ok((reward, outcome)) = state.clone().outcome(action.clone()) else {
+                unreachable!("legal actions should be applicable");
+            };
+
+            // The root outcomes are sampled like the search samples them.
+            let sampled_states = search_constraint.sampling.and_then(|sampling| {
+                let count = sampling.samples_at(0);
+                let states = stack::sample_states::<G>(&outcome, count, sampling.seed())?;
+                Some((states, count))
+            });
+            let (states, sample_count) = match sampled_states {
+                Some((states, count)) => (states, Some(count)),
+                None => {
+                    let states = outcome
+                        .into_iter()
+                        .map(|weighted| (weighted.value, Value::from(weighted.weight)))
+                        .collect();
+                    (states, None)
+                }
+            };
+            let total_weight: Value = states.iter().map(|(_, weight)| weight).sum();
+
+            let states_before = items.len();
+            items.extend(states.into_iter().map(|(state, weight)| WorkItem {
+                action: actions.len(),
+                state,
+                probability: weight / total_weight,
+            }));
+
+            actions.push(ActionWork {
+                action,
+                reward: Value::from(reward),
+                mean_value: WeightedAverage::default(),
+                min_depth: MaxDepth::Unlimited,
+                truncated: false,
+                cutoff: 0.0,
+                sampled: sample_count.is_some(),
+                samples: sample_count.map_or(u16::MAX, |count| count as u16),
+                sample_count,
+                squares: 0.0,
+                variances: 0.0,
+                nodes: 0,
+                pending: items.len() - states_before,
+            });
+        }
+
+        let nodes_before = stats.nodes;
+        // The root decision node
+        stats.nodes += 1;
+
+        let mut result = Ok(());
+        // Root action and probability of the item searched by each busy searcher
+        let mut busy_tasks: HashMap<usize, (usize, Value)> = HashMap::new();
+
+        loop {
+            // Hand out items to the idle searchers.
+            while result.is_ok() && busy_tasks.len() < searchers {
+                let Some(item) = items.pop_front() else {
+                    break;
+                };
+
+                let used_nodes = stats.nodes - nodes_before;
+                let max_nodes = search_constraint
+                    .max_nodes
+                    .map(|max| max.saturating_sub(used_nodes));
+                if max_nodes == Some(0) {
+                    result = Err(SearchError::NodeBudget);
+                    break;
+                }
+
+                let task_id = (0..searchers)
+                    .find(|task_id| !busy_tasks.contains_key(task_id))
+                    .expect("there should be an idle searcher");
+
+                let task = Task {
+                    task_id,
+                    state: item.state,
+                    search_constraint: SearchConstraint {
+                        max_depth: item_depth,
+                        max_nodes,
+                        // The probabilities of the search are relative to the item.
+                        min_probability: search_constraint
+                            .min_probability
+                            .map(|min_probability| min_probability / item.probability),
+                        sampling: search_constraint
+                            .sampling
+                            .map(|sampling| sampling.deeper(1)),
+                        ..search_constraint
+                    },
+                    canonical_cache: self.canonical_cache,
+                    cancellation: cancellation.clone(),
+                };
+                self.send_task(task);
+
+                busy_tasks.insert(task_id, (item.action, item.probability));
+            }
+
+            if busy_tasks.is_empty() {
+                break;
+            }
+
+            let search = self.receive_merged(stats);
+            let (action, probability) = busy_tasks
+                .remove(&search.task_id)
+                .expect("results should only come from busy searchers");
+
+            match search.result {
+                Ok(decision) => {
+                    let nodes = search.stats.nodes;
+                    actions[action].add(probability, decision.eval(), nodes);
+                }
+                Err(err) => {
+                    if result.is_ok() {
+                        result = Err(err);
+                    }
+                }
+            }
+        }
+
+        let evaluated_actions = actions
+            .into_iter()
+            .filter(|action| action.pending == 0)
+            .map(ActionWork::evaluated)
+            .collect();
+
+        (result, evaluated_actions)
+    }
+
+    /// Waits for the next result, and adds its stats to `stats` without its iteration.
+    fn receive_merged(&mut self, stats: &mut SearchStats) -> SearchResult<G> {
+        let search = self.receive_result();
+
+        let mut search_stats = search.stats.clone();
+        search_stats.iterations.clear();
+        stats.merge(search_stats);
+
+        search
+    }
+}
+
+/// Best of the evaluated root actions, [`Decision::Resign`] if there are none.
+fn partial_decision<A: Clone>(evaluated_actions: &[EvaluatedAction<A>]) -> Decision<A> {
+    evaluated_actions
+        .iter()
+        .cloned()
+        .map(Decision::Act)
+        .fold(Decision::Resign, Decision::max_by_eval)
+}
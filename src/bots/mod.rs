@@ -1,2 +1,11 @@
 pub mod heuristic;
+pub mod mcts;
 pub mod mean_max;
+
+use crate::game;
+use mean_max::searcher::{Decision, SearchConstraint};
+
+/// A bot picking the actions of a game, so bots can be swapped for each other.
+pub trait Bot<G: game::GameState> {
+    fn decide_until(&mut self, state: &G, constraint: SearchConstraint) -> Decision<G::Action>;
+}
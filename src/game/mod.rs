@@ -2,8 +2,13 @@ pub mod twenty_forty_eight;
 
 use crate::accumulator::fraction::Weighted;
 
-pub trait Outcome<G: GameState> {
-    fn collapse(self) -> G;
+pub trait Outcome<G: GameState>: Sized {
+    /// Samples one of the possible states using the given random number generator.
+    fn collapse_with<R: rand::Rng + ?Sized>(self, rng: &mut R) -> G;
+
+    fn collapse(self) -> G {
+        self.collapse_with(&mut rand::rng())
+    }
 }
 
 pub trait GameState: Sized
@@ -15,7 +20,41 @@ where
     type Reward;
 
     fn is_terminal(&self) -> bool;
-    fn outcome(self, action: Self::Action) -> (Self::Reward, Self::Outcome);
+
+    /// Returns `true` if `action` can be applied to the state.
+    fn is_legal(&self, action: &Self::Action) -> bool;
+
+    /// Returns the actions that can be applied to the state.
+    fn legal_actions(&self) -> impl Iterator<Item = Self::Action>
+    where
+        Self::Action: Discrete,
+    {
+        Self::Action::iter().filter(|action| self.is_legal(action))
+    }
+
+    /// Applies the action, returning the reward and the possible next states.
+    ///
+    /// # Errors
+    ///
+    /// Returns [`IllegalAction`] if the action can't be applied to the state.
+    fn outcome(self, action: Self::Action) -> OutcomeResult<Self>;
+}
+
+pub type OutcomeResult<G> = Result<
+    (<G as GameState>::Reward, <G as GameState>::Outcome),
+    IllegalAction<<G as GameState>::Action>,
+>;
+
+#[derive(thiserror::Error, Debug, Clone, Copy, PartialEq, Eq)]
+#[error("action {action} is not legal in this state")]
+pub struct IllegalAction<A> {
+    pub action: A,
+}
+
+/// Values that have symmetric variants which are interchangeable (e.g. have the same evaluation).
+pub trait Canonical {
+    /// Returns the same value for all the symmetric variants.
+    fn canonical(&self) -> Self;
 }
 
 pub trait Discrete: Sized {
@@ -0,0 +1,643 @@
+//! A 4x4 board packed in a `u64` of 4-bit exponents.
+//!
+//! Cell `(row, col)` is stored in the nibble at bit `16 * row + 4 * col`. Swipes in every direction
+//! are done with one table lookup per row (or column), the tables are built on first use.
+//!
+//! Bitboards always use the default [`SpawnRules`].
+
+use super::board::{Cell, Cells, Direction, SpawnRules, SwipeResult, Symmetry, Weight};
+use crate::accumulator::fraction::Weighted;
+use crate::game::{IllegalAction, OutcomeResult};
+use rand::distr::{weighted::WeightedIndex, Distribution as _};
+use std::fmt::{self, Display};
+use std::sync::LazyLock;
+
+/// Largest exponent that fits in a nibble, two of these don't merge.
+///
+/// [`Cells`] merge them, so a bitboard only plays the same game until the first 32768 tile.
+pub const MAX_CELL: Cell = 0xF;
+
+const ROW_MASK: u64 = 0xFFFF;
+const COL_MASK: u64 = 0x000F_000F_000F_000F;
+
+#[derive(Copy, Clone, Debug, Default, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub struct BitBoard(pub u64);
+
+#[derive(thiserror::Error, Debug, Clone, Copy, PartialEq, Eq)]
+#[error("cell exponent {0} does not fit in a nibble (max is {MAX_CELL})")]
+pub struct CellOverflow(pub Cell);
+
+#[derive(thiserror::Error, Debug, Clone, Copy, PartialEq, Eq)]
+pub enum StateConversionError {
+    #[error(transparent)]
+    CellOverflow(#[from] CellOverflow),
+
+    #[error("bitboards only support the default spawn rules, got {0:?}")]
+    SpawnRules(SpawnRules),
+}
+
+#[derive(Copy, Clone, Default)]
+struct RowEntry {
+    swiped: u16,
+    score: u32,
+}
+
+#[derive(Copy, Clone, Default)]
+struct ColumnEntry {
+    swiped: u64,
+    score: u32,
+}
+
+struct Tables {
+    left: Box<[RowEntry]>,
+    right: Box<[RowEntry]>,
+    up: Box<[ColumnEntry]>,
+    down: Box<[ColumnEntry]>,
+}
+
+static TABLES: LazyLock<Tables> = LazyLock::new(Tables::new);
+
+fn unpack_row(row: u16) -> [Cell; 4] {
+    std::array::from_fn(|i| (row >> (4 * i) & 0xF) as Cell)
+}
+
+fn pack_row(cells: [Cell; 4]) -> u16 {
+    cells
+        .iter()
+        .enumerate()
+        .fold(0, |row, (i, &cell)| row | u16::from(cell) << (4 * i))
+}
+
+/// Spreads the nibbles of a row over the nibbles of the first column.
+fn unpack_column(row: u16) -> u64 {
+    let row = u64::from(row);
+    (row | row << 12 | row << 24 | row << 36) & COL_MASK
+}
+
+fn swipe_row_left(row: [Cell; 4]) -> ([Cell; 4], u32) {
+    let mut swiped = [0; 4];
+    let mut score = 0;
+    let mut len = 0;
+    let mut can_merge = false;
+
+    for cell in row.into_iter().filter(|&cell| cell != 0) {
+        if can_merge && swiped[len - 1] == cell && cell < MAX_CELL {
+            swiped[len - 1] += 1;
+            score += 2u32.pow(swiped[len - 1].into());
+            can_merge = false;
+        } else {
+            swiped[len] = cell;
+            len += 1;
+            can_merge = true;
+        }
+    }
+
+    (swiped, score)
+}
+
+impl Tables {
+    fn new() -> Self {
+        let size = usize::from(u16::MAX) + 1;
+        let mut left = vec![RowEntry::default(); size].into_boxed_slice();
+        let mut right = vec![RowEntry::default(); size].into_boxed_slice();
+        let mut up = vec![ColumnEntry::default(); size].into_boxed_slice();
+        let mut down = vec![ColumnEntry::default(); size].into_boxed_slice();
+
+        for row in 0..=u16::MAX {
+            let cells = unpack_row(row);
+            let (swiped, score) = swipe_row_left(cells);
+            let swiped = pack_row(swiped);
+            left[usize::from(row)] = RowEntry { swiped, score };
+            up[usize::from(row)] = ColumnEntry {
+                swiped: unpack_column(swiped),
+                score,
+            };
+
+            let mut reversed = cells;
+            reversed.reverse();
+            let (mut swiped, score) = swipe_row_left(reversed);
+            swiped.reverse();
+            let swiped = pack_row(swiped);
+            right[usize::from(row)] = RowEntry { swiped, score };
+            down[usize::from(row)] = ColumnEntry {
+                swiped: unpack_column(swiped),
+                score,
+            };
+        }
+
+        Self {
+            left,
+            right,
+            up,
+            down,
+        }
+    }
+}
+
+impl BitBoard {
+    pub const fn new() -> Self {
+        Self(0)
+    }
+
+    pub fn get(self, row: usize, col: usize) -> Cell {
+        (self.0 >> (16 * row + 4 * col) & 0xF) as Cell
+    }
+
+    pub fn set(&mut self, row: usize, col: usize, cell: Cell) {
+        let shift = 16 * row + 4 * col;
+        self.0 = self.0 & !(0xF << shift) | (u64::from(cell) & 0xF) << shift;
+    }
+
+    fn row(self, row: usize) -> u16 {
+        (self.0 >> (16 * row) & ROW_MASK) as u16
+    }
+
+    /// Swaps rows and columns.
+    pub fn transposed(self) -> Self {
+        let x = self.0;
+        let a1 = x & 0xF0F0_0F0F_F0F0_0F0F;
+        let a2 = x & 0x0000_F0F0_0000_F0F0;
+        let a3 = x & 0x0F0F_0000_0F0F_0000;
+        let a = a1 | a2 << 12 | a3 >> 12;
+        let b1 = a & 0xFF00_FF00_00FF_00FF;
+        let b2 = a & 0x00FF_00FF_0000_0000;
+        let b3 = a & 0x0000_0000_FF00_FF00;
+        Self(b1 | b2 >> 24 | b3 << 24)
+    }
+
+    /// Mirrors the board left to right.
+    pub fn flipped_horizontally(self) -> Self {
+        let x = self.0;
+        let x = (x & 0x0F0F_0F0F_0F0F_0F0F) << 4 | (x >> 4) & 0x0F0F_0F0F_0F0F_0F0F;
+        let x = (x & 0x00FF_00FF_00FF_00FF) << 8 | (x >> 8) & 0x00FF_00FF_00FF_00FF;
+        Self(x)
+    }
+
+    /// Mirrors the board top to bottom.
+    pub fn flipped_vertically(self) -> Self {
+        let x = self.0.rotate_left(32);
+        let x = (x & 0x0000_FFFF_0000_FFFF) << 16 | (x >> 16) & 0x0000_FFFF_0000_FFFF;
+        Self(x)
+    }
+
+    pub fn transformed(self, symmetry: Symmetry) -> Self {
+        let mut board = self;
+        if symmetry.transposes() {
+            board = board.transposed();
+        }
+
+        // Transposing is already applied, what's left is flipping.
+        match symmetry {
+            Symmetry::Identity | Symmetry::Transpose => board,
+            Symmetry::FlipHorizontal | Symmetry::RotateClockwise => board.flipped_horizontally(),
+            Symmetry::FlipVertical | Symmetry::RotateCounterClockwise => board.flipped_vertically(),
+            Symmetry::Rotate180 | Symmetry::AntiTranspose => {
+                board.flipped_vertically().flipped_horizontally()
+            }
+        }
+    }
+
+    /// Returns the smallest (as a `u64`) of all the symmetric boards and the symmetry that
+    /// produces it.
+    pub fn canonical(self) -> (Self, Symmetry) {
+        Symmetry::ALL
+            .into_iter()
+            .map(|symmetry| (self.transformed(symmetry), symmetry))
+            .min_by_key(|(board, _symmetry)| *board)
+            .expect("there is at least one symmetry")
+    }
+
+    pub fn count_empty(self) -> usize {
+        // Fold every nibble into its lowest bit, the bit is set if the nibble is not empty.
+        let mut x = self.0;
+        x |= x >> 2;
+        x |= x >> 1;
+        16 - (x & 0x1111_1111_1111_1111).count_ones() as usize
+    }
+
+    /// Sum of the exponents of the tiles.
+    pub fn exponent_sum(self) -> u32 {
+        // Add the nibbles in pairs into bytes, then add up the bytes in the top byte.
+        let bytes = (self.0 & 0x0F0F_0F0F_0F0F_0F0F) + (self.0 >> 4 & 0x0F0F_0F0F_0F0F_0F0F);
+        (bytes.wrapping_mul(0x0101_0101_0101_0101) >> 56) as u32
+    }
+
+    fn swipe_rows(&mut self, table: &[RowEntry]) -> u32 {
+        let mut swiped = 0;
+        let mut score = 0;
+        for row in 0..4 {
+            let entry = table[usize::from(self.row(row))];
+            swiped |= u64::from(entry.swiped) << (16 * row);
+            score += entry.score;
+        }
+
+        self.0 = swiped;
+        score
+    }
+
+    fn swipe_columns(&mut self, table: &[ColumnEntry]) -> u32 {
+        let transposed = self.transposed();
+        let mut swiped = 0;
+        let mut score = 0;
+        for col in 0..4 {
+            let entry = table[usize::from(transposed.row(col))];
+            swiped |= entry.swiped << (4 * col);
+            score += entry.score;
+        }
+
+        self.0 = swiped;
+        score
+    }
+
+    pub fn swipe(&mut self, direction: Direction) -> SwipeResult {
+        let tables = &*TABLES;
+        let before = *self;
+        let score = match direction {
+            Direction::Left => self.swipe_rows(&tables.left),
+            Direction::Right => self.swipe_rows(&tables.right),
+            Direction::Up => self.swipe_columns(&tables.up),
+            Direction::Down => self.swipe_columns(&tables.down),
+        };
+
+        if before == *self {
+            SwipeResult::Unchanged
+        } else {
+            SwipeResult::Changed { score }
+        }
+    }
+
+    /// Returns the swiped board and the score of the swipe, or `None` if the swipe didn't change
+    /// the board.
+    #[must_use]
+    pub fn swiped(mut self, direction: Direction) -> Option<(Self, u32)> {
+        let result = self.swipe(direction);
+        result.is_changed().then_some((self, result.score()))
+    }
+
+    #[must_use]
+    pub fn has_move(self) -> bool {
+        // Swiping right changes the board iff swiping left does, same for up and down.
+        self.count_empty() > 0
+            || self.swiped(Direction::Left).is_some()
+            || self.swiped(Direction::Up).is_some()
+    }
+
+    #[must_use]
+    pub fn is_lost(self) -> bool {
+        !self.has_move()
+    }
+}
+
+impl From<BitBoard> for Cells<4, 4> {
+    fn from(board: BitBoard) -> Self {
+        Cells::from_cells(std::array::from_fn(|row| unpack_row(board.row(row))))
+    }
+}
+
+impl TryFrom<Cells<4, 4>> for BitBoard {
+    type Error = CellOverflow;
+
+    fn try_from(cells: Cells<4, 4>) -> Result<Self, Self::Error> {
+        if let Some(&cell) = cells.as_flattened().iter().find(|&&cell| cell > MAX_CELL) {
+            return Err(CellOverflow(cell));
+        }
+
+        let board = cells.rows().enumerate().fold(0, |board, (i, row)| {
+            board | u64::from(pack_row(row)) << (16 * i)
+        });
+
+        Ok(BitBoard(board))
+    }
+}
+
+impl Display for BitBoard {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        Display::fmt(&Cells::from(*self), f)
+    }
+}
+
+/// Iterates over every board with one more tile, in the same order as [`super::board::Spawns`].
+#[derive(Debug, Clone)]
+pub struct Spawns {
+    board: BitBoard,
+    position: u8,
+    exponent: Cell,
+}
+
+impl Spawns {
+    pub fn new(board: BitBoard) -> Self {
+        Spawns {
+            board,
+            position: 0,
+            exponent: 2,
+        }
+    }
+
+    pub fn empty() -> Self {
+        Spawns {
+            board: BitBoard::new(),
+            position: 0,
+            exponent: 0,
+        }
+    }
+
+    fn is_empty_at(&self, position: u8) -> bool {
+        self.board.0 >> (4 * position) & 0xF == 0
+    }
+}
+
+impl Iterator for Spawns {
+    type Item = Weighted<BitBoard, Weight>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        while self.exponent > 0 {
+            let position = self.position;
+            let exponent = self.exponent;
+
+            self.position += 1;
+            if self.position == 16 {
+                self.position = 0;
+                self.exponent -= 1;
+            }
+
+            if self.is_empty_at(position) {
+                let value = BitBoard(self.board.0 | u64::from(exponent) << (4 * position));
+                let weight = Weight::new(if exponent == 1 { 2 } else { 1 });
+                return weight.map(|weight| Weighted { value, weight });
+            }
+        }
+
+        None
+    }
+
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        let remaining = match self.exponent {
+            0 => 0,
+            exponent => {
+                let in_pass = (self.position..16)
+                    .filter(|&position| self.is_empty_at(position))
+                    .count();
+
+                in_pass + usize::from(exponent - 1) * self.board.count_empty()
+            }
+        };
+
+        (remaining, Some(remaining))
+    }
+}
+
+impl ExactSizeIterator for Spawns {}
+
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Hash)]
+pub struct State {
+    pub board: BitBoard,
+}
+
+impl State {
+    pub fn new() -> Self {
+        Self::new_with_rng(&mut rand::rng())
+    }
+
+    pub fn new_with_rng<R: rand::Rng + ?Sized>(rng: &mut R) -> Self {
+        let state = super::State::<4, 4>::new_with_rng(rng);
+        Self::try_from(state).expect("a new state only has small tiles")
+    }
+}
+
+impl crate::game::GameState for State {
+    type Outcome = Outcome;
+    type Action = Direction;
+    type Reward = f32;
+
+    fn outcome(self, action: Self::Action) -> OutcomeResult<Self> {
+        let (board, score) = self.board.swiped(action).ok_or(IllegalAction { action })?;
+        Ok((score as f32, Outcome { board }))
+    }
+
+    fn is_terminal(&self) -> bool {
+        self.board.is_lost()
+    }
+
+    fn is_legal(&self, action: &Self::Action) -> bool {
+        self.board.swiped(*action).is_some()
+    }
+}
+
+impl Display for State {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        Display::fmt(&self.board, f)
+    }
+}
+
+impl From<State> for super::State<4, 4> {
+    fn from(state: State) -> Self {
+        super::State::from_cells(Cells::from(state.board))
+    }
+}
+
+impl TryFrom<super::State<4, 4>> for State {
+    type Error = StateConversionError;
+
+    fn try_from(state: super::State<4, 4>) -> Result<Self, Self::Error> {
+        if state.rules != SpawnRules::default() {
+            return Err(StateConversionError::SpawnRules(state.rules));
+        }
+
+        let board = BitBoard::try_from(state.cells)?;
+        Ok(Self { board })
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, PartialOrd, Ord)]
+pub struct Outcome {
+    pub(crate) board: BitBoard,
+}
+
+impl Display for Outcome {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        Display::fmt(&self.board, f)
+    }
+}
+
+impl From<Outcome> for super::Outcome<4, 4> {
+    fn from(outcome: Outcome) -> Self {
+        super::Outcome {
+            cells: Cells::from(outcome.board),
+            rules: SpawnRules::default(),
+        }
+    }
+}
+
+impl IntoIterator for Outcome {
+    type Item = Weighted<State, Weight>;
+    type IntoIter = std::iter::Map<Spawns, fn(<Spawns as Iterator>::Item) -> Self::Item>;
+
+    fn into_iter(self) -> Self::IntoIter {
+        Spawns::new(self.board).map(|weighted| weighted.map(|board| State { board }))
+    }
+}
+
+impl crate::game::Canonical for Outcome {
+    fn canonical(&self) -> Self {
+        Outcome {
+            board: self.board.canonical().0,
+        }
+    }
+}
+
+impl crate::game::DiscreteDistribution for Outcome {
+    type T = State;
+    type Weight = Weight;
+}
+
+impl crate::game::Outcome<State> for Outcome {
+    fn collapse_with<R: rand::Rng + ?Sized>(self, rng: &mut R) -> State {
+        let (weights, items): (Vec<_>, Vec<_>) = self
+            .into_iter()
+            .map(|weighted| (weighted.weight.get(), weighted.value))
+            .unzip();
+
+        match WeightedIndex::new(weights) {
+            Ok(weighted_index) => items[weighted_index.sample(rng)],
+            Err(err) => panic!(
+                "Failed to collapse outcome: {err}\noutcome:\n{}",
+                self.board
+            ),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{BitBoard, CellOverflow, Spawns};
+    use crate::game::twenty_forty_eight::board::{Cells, Direction, Spawns as CellSpawns};
+    use crate::game::Discrete as _;
+
+    fn random_cells(rng: &mut impl rand::Rng, max_cell: u8) -> Cells<4, 4> {
+        Cells::from_cells(std::array::from_fn(|_| {
+            std::array::from_fn(|_| rng.random_range(0..=max_cell))
+        }))
+    }
+
+    #[test]
+    fn test_conversions() {
+        let mut rng = rand::rng();
+        for _ in 0..1000 {
+            let cells = random_cells(&mut rng, 15);
+            let board = BitBoard::try_from(cells).unwrap();
+            assert_eq!(Cells::from(board), cells);
+            assert_eq!(Cells::from(board.transposed()), cells.transposed());
+            assert_eq!(board.count_empty(), cells.count_empty(), "Input:\n{cells}");
+            // Two 15s can't merge on a bitboard.
+            if !cells.as_flattened().contains(&15) {
+                assert_eq!(board.is_lost(), cells.is_lost(), "Input:\n{cells}");
+            }
+
+            let exponent_sum: u32 = cells.as_flattened().iter().copied().map(u32::from).sum();
+            assert_eq!(board.exponent_sum(), exponent_sum);
+        }
+
+        let mut cells = Cells::new();
+        cells[2][1] = 16;
+        assert_eq!(BitBoard::try_from(cells), Err(CellOverflow(16)));
+    }
+
+    #[test]
+    fn test_swipe() {
+        let mut rng = rand::rng();
+        // Keep cells below 15 since two 15s don't merge on a bitboard.
+        for max_cell in [6, 14] {
+            for _ in 0..5000 {
+                let cells = random_cells(&mut rng, max_cell);
+                let board = BitBoard::try_from(cells).unwrap();
+
+                for direction in Direction::iter() {
+                    let mut swiped_cells = cells;
+                    let mut swiped_board = board;
+                    let expected = swiped_cells.swipe(direction);
+                    let result = swiped_board.swipe(direction);
+
+                    assert_eq!(result, expected, "Input:\n{cells}\nDirection: {direction}");
+                    assert_eq!(
+                        Cells::from(swiped_board),
+                        swiped_cells,
+                        "Input:\n{cells}\nDirection: {direction}"
+                    );
+                    assert_eq!(
+                        cells.can_swipe(direction),
+                        expected.is_changed(),
+                        "Input:\n{cells}\nDirection: {direction}"
+                    );
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn test_symmetries() {
+        use crate::game::twenty_forty_eight::board::Symmetry;
+
+        let mut rng = rand::rng();
+        for _ in 0..1000 {
+            let cells = random_cells(&mut rng, 15);
+            let board = BitBoard::try_from(cells).unwrap();
+
+            for symmetry in Symmetry::iter() {
+                let expected = cells.transformed(symmetry).unwrap();
+                let transformed = board.transformed(symmetry);
+                assert_eq!(Cells::from(transformed), expected, "Symmetry: {symmetry:?}");
+                assert_eq!(transformed.canonical().0, board.canonical().0);
+            }
+        }
+    }
+
+    #[test]
+    fn test_max_cells_do_not_merge() {
+        let mut board = BitBoard::new();
+        board.set(0, 0, 15);
+        board.set(0, 1, 15);
+        assert!(board.swiped(Direction::Left).is_none());
+        assert!(board.swiped(Direction::Right).is_some());
+
+        // The cells merge them into a tile that doesn't fit in a bitboard.
+        let mut cells = Cells::from(board);
+        assert!(cells.swipe(Direction::Left).is_changed());
+        assert_eq!(BitBoard::try_from(cells), Err(CellOverflow(16)));
+    }
+
+    #[test]
+    fn test_spawns() {
+        let mut rng = rand::rng();
+        for _ in 0..1000 {
+            let cells = random_cells(&mut rng, 3);
+            let board = BitBoard::try_from(cells).unwrap();
+            let mut spawns = Spawns::new(board);
+            let mut cell_spawns = CellSpawns::new(cells);
+            assert_eq!(spawns.len(), cell_spawns.len(), "Input:\n{cells}");
+
+            while let Some(expected) = cell_spawns.next() {
+                let spawn = spawns.next().unwrap();
+                assert_eq!(Cells::from(spawn.value), expected.value);
+                assert_eq!(spawn.weight.get(), expected.weight.get());
+                assert_eq!(spawns.len(), cell_spawns.len());
+            }
+
+            assert!(spawns.next().is_none());
+        }
+    }
+
+    #[test]
+    fn test_search() {
+        use crate::bots::mean_max::{max_depth::MaxDepth, searcher::SearchConstraint, MeanMax};
+        use crate::game::twenty_forty_eight::{bitboard, State};
+
+        let state = State::from_cells([[3, 3, 1, 1], [1, 0, 5, 0], [0, 2, 7, 4], [6, 1, 6, 8]]);
+        let bit_state = bitboard::State::try_from(state.clone()).unwrap();
+        let constraint = SearchConstraint::new().with_max_depth(MaxDepth::new(2));
+
+        let (decision, _) = MeanMax::new().decide_until(&state, constraint);
+        let (bit_decision, _) = MeanMax::default().decide_until(&bit_state, constraint);
+
+        assert_eq!(bit_decision, decision);
+    }
+}
@@ -1,6 +1,11 @@
 use std::num::NonZeroU8;
 
-pub fn swipe_left_4_fast(mut bytes: u32) -> u32 {
+/// Swipes a row of 4 cells packed in a little-endian `u32` to the left.
+///
+/// Returns the swiped row and the score of the merges (sum of the merged tiles).
+pub fn swipe_left_4_fast(mut bytes: u32) -> (u32, u32) {
+    let mut score = 0;
+
     // Early return if all are zeros
     // if bytes == 0 {
     //     return bytes;
@@ -27,41 +32,50 @@ pub fn swipe_left_4_fast(mut bytes: u32) -> u32 {
     // println!("shift3: 0x{bytes:08x} ({:?})", bytes.to_be_bytes());
     // Early return if block[0] is empty (meaning all blocks were zero)
     if bytes & 0xFF == 0 {
-        return bytes;
+        return (bytes, score);
     }
     // Merge block[0] and block[1] if possible
     if (bytes ^ (bytes >> 8)) & 0xFF == 0 {
         bytes = (bytes >> 8) + 1;
+        score += merge_score(bytes as u8);
     }
 
     // Early return if block[1] is empty (meaning the rest of blocks are zero)
     if bytes & 0xFF00 == 0 {
-        return bytes;
+        return (bytes, score);
     }
     // Merge block[1] and block[2] if possible
     if (bytes ^ (bytes >> 8)) & 0xFF00 == 0 {
         bytes = (bytes >> 8) & !0xFF | (bytes & 0xFF);
         bytes += 1 << 8;
+        score += merge_score((bytes >> 8) as u8);
     }
 
     // Early return if block[1] is empty (meaning the rest of blocks are zero)
     if bytes & 0xFF0000 == 0 {
-        return bytes;
+        return (bytes, score);
     }
 
     // Merge block[2] and block[3] if possible
     if (bytes ^ (bytes >> 8)) & 0xFF0000 == 0 {
         bytes = (bytes >> 8) & !0xFFFF | (bytes & 0xFFFF);
         bytes += 1 << 16;
+        score += merge_score((bytes >> 16) as u8);
     }
 
-    bytes
+    (bytes, score)
+}
+
+/// Score of a merge that resulted in a block with the given exponent.
+#[inline]
+pub(super) fn merge_score(merged_block: u8) -> u32 {
+    2u32.pow(merged_block.into())
 }
 
 type Block = NonZeroU8;
 
 #[derive(Copy, Clone, Debug, Default, PartialEq, Eq)]
-enum SwipeResult {
+pub enum SwipeResult {
     #[default]
     Unchanged,
     Changed {
@@ -71,7 +85,7 @@ enum SwipeResult {
 
 impl SwipeResult {
     fn handle_merge(&mut self, merged_block: Block) {
-        let new_score = 2u32.pow(merged_block.get().into());
+        let new_score = merge_score(merged_block.get());
         match self {
             SwipeResult::Unchanged => *self = SwipeResult::Changed { score: new_score },
             SwipeResult::Changed { score } => *score += new_score,
@@ -85,27 +99,65 @@ impl SwipeResult {
         }
     }
 
-    /// Returns `true` if the swipe result is [`UnChanged`].
+    /// Returns `true` if the swipe result is [`Unchanged`].
     ///
-    /// [`UnChanged`]: SwipeResult::UnChanged
+    /// [`Unchanged`]: SwipeResult::Unchanged
     #[must_use]
-    fn is_unchanged(&self) -> bool {
+    pub fn is_unchanged(&self) -> bool {
         matches!(self, Self::Unchanged)
     }
+
+    /// Returns `true` if the swipe result is [`Changed`].
+    ///
+    /// [`Changed`]: SwipeResult::Changed
+    #[must_use]
+    pub fn is_changed(&self) -> bool {
+        !self.is_unchanged()
+    }
+
+    /// Sum of the values of all the merged blocks, `0` if nothing was merged.
+    #[must_use]
+    pub fn score(&self) -> u32 {
+        match self {
+            SwipeResult::Unchanged => 0,
+            SwipeResult::Changed { score } => *score,
+        }
+    }
+
+    /// Combines the results of swiping two independent rows.
+    #[must_use]
+    pub fn combine(self, other: Self) -> Self {
+        match (self, other) {
+            (SwipeResult::Unchanged, other) => other,
+            (this, SwipeResult::Unchanged) => this,
+            (SwipeResult::Changed { score: a }, SwipeResult::Changed { score: b }) => {
+                SwipeResult::Changed { score: a + b }
+            }
+        }
+    }
 }
 
-pub fn swipe_left<const SIZE: usize>(cells: &mut [u8; SIZE]) -> bool {
+pub fn swipe_left<const SIZE: usize>(cells: &mut [u8; SIZE]) -> SwipeResult {
     // TODO: Try this fast implementation for swipe_right.
 
     if let [a, b, c, d] = cells[..] {
-        let new_cells = swipe_left_4_fast(u32::from_le_bytes([a, b, c, d])).to_le_bytes();
+        let (new_cells, score) = swipe_left_4_fast(u32::from_le_bytes([a, b, c, d]));
+        let new_cells = new_cells.to_le_bytes();
 
         // Convert slice to array
         let new_cells = std::array::from_fn(|i| new_cells[i]);
         let old_cells = std::mem::replace(cells, new_cells);
-        return old_cells.ne(cells);
+        return if old_cells.ne(cells) {
+            SwipeResult::Changed { score }
+        } else {
+            SwipeResult::Unchanged
+        };
     }
 
+    swipe_left_generic(cells)
+}
+
+pub(super) fn swipe_left_generic<const SIZE: usize>(cells: &mut [u8; SIZE]) -> SwipeResult {
     let mut last_pos = 0;
     let mut result = SwipeResult::Unchanged;
     let mut blocks = cells.map(Block::new);
@@ -163,10 +215,10 @@ pub fn swipe_left<const SIZE: usize>(cells: &mut [u8; SIZE]) -> bool {
     // }
 
     *cells = new_cells;
-    !result.is_unchanged()
+    result
 }
 
-pub fn swipe_right<const SIZE: usize>(cells: &mut [u8; SIZE]) -> bool {
+pub fn swipe_right<const SIZE: usize>(cells: &mut [u8; SIZE]) -> SwipeResult {
     // PERF: Reverse the implementation of swipe_left for this.
     cells.reverse();
     let swiped = swipe_left(cells);
@@ -196,17 +248,18 @@ mod tests {
         ([2, 1, 0, 1], [2, 2, 0, 0]),
         ([2, 2, 2, 2], [3, 3, 0, 0]),
         ([2, 3, 2, 2], [2, 3, 3, 0]),
+        ([15, 15, 14, 14], [16, 15, 0, 0]),
     ];
 
     fn test_for<const SIZE: usize>(
-        f: impl FnOnce(&mut [u8; SIZE]) -> bool,
+        f: impl FnOnce(&mut [u8; SIZE]) -> SwipeResult,
         inp: [u8; SIZE],
         expected_out: [u8; SIZE],
     ) {
         let mut row = inp;
-        let changed = f(&mut row);
+        let result = f(&mut row);
         assert_eq!(row, expected_out, "input: {inp:?}");
-        assert_eq!(changed, inp != expected_out, "input: {inp:?}");
+        assert_eq!(result.is_changed(), inp != expected_out, "input: {inp:?}");
     }
 
     #[test]
@@ -232,4 +285,33 @@ mod tests {
         test_for(swipe_right, [], []);
         test_for(swipe_right, [1], [1]);
     }
+
+    #[test]
+    fn test_swipe_score() {
+        let score_of = |mut row: [u8; 4]| swipe_left(&mut row).score();
+
+        assert_eq!(score_of([0, 0, 0, 0]), 0);
+        assert_eq!(score_of([1, 2, 3, 4]), 0);
+        assert_eq!(score_of([0, 0, 1, 1]), 4);
+        assert_eq!(score_of([1, 1, 1, 1]), 8);
+        assert_eq!(score_of([2, 2, 2, 2]), 16);
+        assert_eq!(score_of([1, 2, 2, 3]), 8);
+        assert_eq!(score_of([3, 0, 3, 1]), 16);
+        assert_eq!(score_of([10, 10, 11, 0]), 2048);
+    }
+
+    #[test]
+    fn test_fast_matches_generic() {
+        for bytes in 0..6u32.pow(4) {
+            let row: [u8; 4] = std::array::from_fn(|i| (bytes / 6u32.pow(i as u32) % 6) as u8);
+
+            let mut fast_row = row;
+            let mut generic_row = row;
+            let fast = swipe_left(&mut fast_row);
+            let generic = swipe_left_generic(&mut generic_row);
+
+            assert_eq!(fast_row, generic_row, "input: {row:?}");
+            assert_eq!(fast, generic, "input: {row:?}");
+        }
+    }
 }
@@ -1,9 +1,18 @@
 pub mod fast_swipe;
+pub mod parse;
+pub mod simd_swipe;
+pub mod spawn_rules;
+pub mod symmetry;
+
+pub use fast_swipe::SwipeResult;
+pub use parse::ParseCellsError;
+pub use spawn_rules::SpawnRules;
+pub use symmetry::Symmetry;
 
 use crate::accumulator::fraction::Weighted;
 use std::fmt::Write as _;
 use std::marker::PhantomData;
-use std::num::NonZeroU8;
+use std::num::NonZeroU32;
 use std::ops::{Deref, DerefMut};
 use std::simd::{cmp::SimdPartialEq as _, u8x16};
 use std::{array, fmt};
@@ -62,16 +71,16 @@ impl Iterator for Directions {
 }
 
 // TODO: Implement From for {i32, u32, u8, i8, ...}
-// TODO: Change this to an enum of 1/2
+// NOTE: This is a u32 since spawning multiple tiles multiplies their weights.
 #[derive(Debug, Clone, Copy)]
-pub struct Weight(NonZeroU8);
+pub struct Weight(NonZeroU32);
 impl Weight {
-    fn new(n: u8) -> Option<Self> {
-        NonZeroU8::new(n).map(Weight)
+    pub(crate) fn new(n: u32) -> Option<Self> {
+        NonZeroU32::new(n).map(Weight)
     }
 }
 impl Deref for Weight {
-    type Target = NonZeroU8;
+    type Target = NonZeroU32;
 
     fn deref(&self) -> &Self::Target {
         &self.0
@@ -84,7 +93,7 @@ impl DerefMut for Weight {
 }
 impl From<Weight> for f32 {
     fn from(value: Weight) -> Self {
-        f32::from(value.0.get())
+        value.0.get() as f32
     }
 }
 impl From<Weight> for f64 {
@@ -115,45 +124,61 @@ impl<const COLS: usize, const ROWS: usize> Cells<COLS, ROWS> {
         self.into_iter().flatten().filter(|&c| c == 0).count()
     }
 
-    pub fn swipe_left(&mut self) -> bool {
+    pub fn swipe_left(&mut self) -> SwipeResult {
+        if let Some(cells) = self.as_4x4_mut() {
+            return cells.swipe_simd(Direction::Left);
+        }
+
         self.iter_mut()
             .map(fast_swipe::swipe_left)
-            .fold(false, bool::max)
+            .fold(SwipeResult::Unchanged, SwipeResult::combine)
     }
 
-    pub fn swipe_right(&mut self) -> bool {
+    pub fn swipe_right(&mut self) -> SwipeResult {
+        if let Some(cells) = self.as_4x4_mut() {
+            return cells.swipe_simd(Direction::Right);
+        }
+
         self.iter_mut()
             .map(fast_swipe::swipe_right)
-            .fold(false, bool::max)
+            .fold(SwipeResult::Unchanged, SwipeResult::combine)
     }
 
-    pub fn swipe_up(&mut self) -> bool {
+    pub fn swipe_up(&mut self) -> SwipeResult {
+        if let Some(cells) = self.as_4x4_mut() {
+            return cells.swipe_simd(Direction::Up);
+        }
+
         self.columns()
             .enumerate()
             .map(|(i, mut column)| {
-                let moved = fast_swipe::swipe_left(&mut column);
+                let result = fast_swipe::swipe_left(&mut column);
                 column.into_iter().enumerate().for_each(|(j, cell)| {
                     self[j][i] = cell;
                 });
 
-                moved
+                result
             })
-            .fold(false, bool::max)
+            .fold(SwipeResult::Unchanged, SwipeResult::combine)
     }
 
-    pub fn swipe_down(&mut self) -> bool {
+    pub fn swipe_down(&mut self) -> SwipeResult {
+        if let Some(cells) = self.as_4x4_mut() {
+            return cells.swipe_simd(Direction::Down);
+        }
+
         self.columns()
             .enumerate()
             .map(|(i, mut column)| {
                 column.reverse();
-                let moved = fast_swipe::swipe_left(&mut column);
+                let result = fast_swipe::swipe_left(&mut column);
                 column.into_iter().rev().enumerate().for_each(|(j, cell)| {
                     self[j][i] = cell;
                 });
 
-                moved
+                result
             })
-            .fold(false, bool::max)
+            .fold(SwipeResult::Unchanged, SwipeResult::combine)
     }
 
     #[must_use]
@@ -168,7 +193,7 @@ impl<const COLS: usize, const ROWS: usize> Cells<COLS, ROWS> {
             || (0..ROWS).any(|i| (0..COLS - 1).any(|j| self[i][j] == self[i][j + 1]))
     }
 
-    pub fn swipe(&mut self, direction: Direction) -> bool {
+    pub fn swipe(&mut self, direction: Direction) -> SwipeResult {
         match direction {
             Direction::Left => self.swipe_left(),
             Direction::Right => self.swipe_right(),
@@ -177,15 +202,34 @@ impl<const COLS: usize, const ROWS: usize> Cells<COLS, ROWS> {
         }
     }
 
+    /// Returns `true` if swiping in `direction` would change the board, without swiping it.
     #[must_use]
-    pub fn swiped(mut self, direction: Direction) -> Option<Self> {
+    pub fn can_swipe(&self, direction: Direction) -> bool {
+        // A line changes iff a tile can slide into an empty neighbour or merge with an equal one.
+        let can_move = |from: Cell, to: Cell| from != 0 && (to == 0 || to == from);
+
         match direction {
-            Direction::Left => self.swipe_left(),
-            Direction::Right => self.swipe_right(),
-            Direction::Up => self.swipe_up(),
-            Direction::Down => self.swipe_down(),
+            Direction::Left => {
+                (0..ROWS).any(|i| (0..COLS - 1).any(|j| can_move(self[i][j + 1], self[i][j])))
+            }
+            Direction::Right => {
+                (0..ROWS).any(|i| (0..COLS - 1).any(|j| can_move(self[i][j], self[i][j + 1])))
+            }
+            Direction::Up => {
+                (0..ROWS - 1).any(|i| (0..COLS).any(|j| can_move(self[i + 1][j], self[i][j])))
+            }
+            Direction::Down => {
+                (0..ROWS - 1).any(|i| (0..COLS).any(|j| can_move(self[i][j], self[i + 1][j])))
+            }
         }
-        .then_some(self)
+    }
+
+    /// Returns the swiped cells and the score of the swipe, or `None` if the swipe didn't change
+    /// the board.
+    #[must_use]
+    pub fn swiped(mut self, direction: Direction) -> Option<(Self, u32)> {
+        let result = self.swipe(direction);
+        result.is_changed().then_some((self, result.score()))
     }
 
     pub fn transposed(self) -> Cells<ROWS, COLS> {
@@ -201,6 +245,11 @@ impl<const COLS: usize, const ROWS: usize> Cells<COLS, ROWS> {
     pub fn rows(self) -> impl Iterator<Item = [Cell; COLS]> {
         self.into_iter()
     }
+
+    /// Returns the cells as a 4x4 board, to use the SIMD implementations.
+    fn as_4x4_mut(&mut self) -> Option<&mut Cells<4, 4>> {
+        <dyn std::any::Any>::downcast_mut::<Cells<4, 4>>(self)
+    }
 }
 
 impl Cells<4, 4> {
@@ -215,6 +264,12 @@ impl Cells<4, 4> {
         let bytes = unsafe { std::mem::transmute::<[[u8; 4]; 4], [u8; 16]>(self.cells) };
         u8x16::from_array(bytes)
     }
+
+    fn from_simd(simd: u8x16) -> Self {
+        // SAFETY: A [Cell; 16] has the same layout as [[Cell; 4]; 4].
+        let cells = unsafe { std::mem::transmute::<[u8; 16], [[u8; 4]; 4]>(simd.to_array()) };
+        Self::from_cells(cells)
+    }
 }
 
 impl<const COLS: usize, const ROWS: usize> std::hash::Hash for Cells<COLS, ROWS> {
@@ -318,20 +373,140 @@ impl<const COLS: usize, const ROWS: usize> DerefMut for Cells<COLS, ROWS> {
 pub struct Spawns<const COLS: usize, const ROWS: usize> {
     cells: Cells<COLS, ROWS>,
     mask: Cells<COLS, ROWS>,
+    rules: SpawnRules,
+    /// All the spawns, only used when more than one tile spawns per turn.
+    multi_spawns: Option<std::vec::IntoIter<<Self as Iterator>::Item>>,
 }
 
 impl<const COLS: usize, const ROWS: usize> Spawns<COLS, ROWS> {
     pub fn new(cells: Cells<COLS, ROWS>) -> Self {
+        Self::with_rules(cells, SpawnRules::default())
+    }
+
+    pub fn with_rules(cells: Cells<COLS, ROWS>, rules: SpawnRules) -> Self {
+        if rules.tiles_per_turn > 1 {
+            let mut spawns = Self::empty();
+            spawns.rules = rules;
+            spawns.multi_spawns = Some(Self::multi_spawns(cells, rules).into_iter());
+            return spawns;
+        }
+
         let mut mask = Cells::new();
-        mask.cells[0][0] = 2;
-        Spawns { cells, mask }
+        mask.cells[0][0] = rules.next_exponent(Cell::MAX);
+        Spawns {
+            cells,
+            mask,
+            rules,
+            multi_spawns: None,
+        }
     }
 
     pub fn empty() -> Self {
         Spawns {
             cells: Cells::new(),
             mask: Cells::new(),
+            rules: SpawnRules::default(),
+            multi_spawns: None,
+        }
+    }
+
+    pub fn rules(&self) -> SpawnRules {
+        self.rules
+    }
+
+    /// Every way of filling `rules.tiles_per_turn` empty cells (or all of them if there are
+    /// fewer), weighted by the product of the weights of the spawned exponents.
+    fn multi_spawns(cells: Cells<COLS, ROWS>, rules: SpawnRules) -> Vec<<Self as Iterator>::Item> {
+        fn spawn_from<const COLS: usize, const ROWS: usize>(
+            cells: Cells<COLS, ROWS>,
+            rules: &SpawnRules,
+            start: usize,
+            tiles: usize,
+            weight: u32,
+            spawns: &mut Vec<Weighted<Cells<COLS, ROWS>, Weight>>,
+        ) {
+            if tiles == 0 {
+                spawns.extend(
+                    Weight::new(weight).map(|weight| Weighted::new_weighted(cells, weight)),
+                );
+                return;
+            }
+
+            for position in start..COLS * ROWS {
+                if cells.as_flattened()[position] != 0 {
+                    continue;
+                }
+
+                for (exponent, exponent_weight) in rules.exponents() {
+                    let mut new_cells = cells;
+                    new_cells.as_flattened_mut()[position] = exponent;
+                    let weight = weight.checked_mul(exponent_weight.into()).expect(
+                        "spawn weights fit in a u32 up to `SpawnRules::MAX_TILES_PER_TURN`",
+                    );
+                    spawn_from(new_cells, rules, position + 1, tiles - 1, weight, spawns);
+                }
+            }
+        }
+
+        let tiles = cells.count_empty().min(rules.tiles_per_turn.into());
+        let mut spawns = Vec::new();
+        if tiles > 0 {
+            spawn_from(cells, &rules, 0, tiles, 1, &mut spawns);
+        }
+
+        spawns
+    }
+
+    /// Visits the same spawns in the same order as [`Spawns::fast_next`], for any board size.
+    ///
+    /// The mask holds a single non-zero cell, the exponent of the next spawn. It walks over every
+    /// position once for each exponent that can spawn, from the highest to the lowest.
+    fn generic_next(&mut self) -> Option<<Self as Iterator>::Item> {
+        loop {
+            let mask = self.mask.cells.as_flattened_mut();
+            let position = mask.iter().position(|&cell| cell != 0)?;
+            let exponent = std::mem::take(&mut mask[position]);
+
+            if let Some(next) = mask.get_mut(position + 1) {
+                *next = exponent;
+            } else {
+                mask[0] = self.rules.next_exponent(exponent);
+            }
+
+            if self.cells.as_flattened()[position] != 0 {
+                continue;
+            }
+
+            let mut value = self.cells;
+            value.cells.as_flattened_mut()[position] = exponent;
+            let weight = Weight::new(self.rules.weight(exponent).into());
+
+            return weight.map(|weight| Weighted { value, weight });
+        }
+    }
+
+    /// Number of spawns left in the iterator.
+    fn remaining(&self) -> usize {
+        if let Some(multi_spawns) = &self.multi_spawns {
+            return multi_spawns.len();
         }
+
+        let mask = self.mask.as_flattened();
+        let Some(position) = mask.iter().position(|&cell| cell != 0) else {
+            return 0;
+        };
+
+        let count_empty = |cells: &[Cell]| cells.iter().filter(|&&cell| cell == 0).count();
+        let cells = self.cells.as_flattened();
+        let exponent = mask[position];
+        let lower_exponents = self
+            .rules
+            .exponents()
+            .filter(|&(lower, _weight)| lower < exponent)
+            .count();
+
+        // Every exponent has another pass for each exponent below it.
+        count_empty(&cells[position..]) + lower_exponents * count_empty(cells)
     }
 }
 
@@ -382,22 +557,33 @@ impl<const COLS: usize, const ROWS: usize> Iterator for Spawns<COLS, ROWS> {
     type Item = Weighted<Cells<COLS, ROWS>, Weight>;
 
     fn next(&mut self) -> Option<Self::Item> {
+        if let Some(multi_spawns) = &mut self.multi_spawns {
+            return multi_spawns.next();
+        }
+
+        if !self.rules.is_default_spawn() {
+            return self.generic_next();
+        }
+
         if let Some(spawns) = <dyn std::any::Any>::downcast_mut::<Spawns<4, 4>>(self) {
             let result = spawns.fast_next();
             return *<dyn std::any::Any>::downcast_ref(&result).unwrap();
         }
 
-        // TODO:
-        todo!()
+        self.generic_next()
     }
 
-    // TODO: Implement size_hint?
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        let remaining = self.remaining();
+        (remaining, Some(remaining))
+    }
 }
 
-// TODO: Write a macro for creating boards
+impl<const COLS: usize, const ROWS: usize> ExactSizeIterator for Spawns<COLS, ROWS> {}
+
 #[cfg(test)]
 mod test_board {
-    use super::{Cells, Spawns, Weight};
+    use super::{Cells, SpawnRules, Spawns, Weight};
     use crate::accumulator::fraction::Weighted;
     use itertools::Itertools;
     use std::sync::Once;
@@ -473,7 +659,11 @@ mod test_board {
 
             {
                 let mut cells = inp;
-                assert_eq!(cells.swipe_left(), inp != expected_out, "Input: {inp:?}");
+                assert_eq!(
+                    cells.swipe_left().is_changed(),
+                    inp != expected_out,
+                    "Input: {inp:?}"
+                );
                 assert_eq!(cells, expected_out, "Input: {inp:?}");
             }
             {
@@ -481,7 +671,11 @@ mod test_board {
                 let expected_out = Cells::from_cells(expected_out.map(reversed));
 
                 let mut cells = inp;
-                assert_eq!(cells.swipe_right(), inp != expected_out, "Input: {inp:?}");
+                assert_eq!(
+                    cells.swipe_right().is_changed(),
+                    inp != expected_out,
+                    "Input: {inp:?}"
+                );
                 assert_eq!(cells, expected_out, "Input: {inp:?}");
             }
             {
@@ -489,7 +683,11 @@ mod test_board {
                 let expected_out = expected_out.transposed();
 
                 let mut cells = inp;
-                assert_eq!(cells.swipe_up(), inp != expected_out, "Input: {inp:?}");
+                assert_eq!(
+                    cells.swipe_up().is_changed(),
+                    inp != expected_out,
+                    "Input: {inp:?}"
+                );
                 assert_eq!(cells, expected_out, "Input: {inp:?}");
             }
             {
@@ -497,7 +695,11 @@ mod test_board {
                 let expected_out = Cells::from_cells(expected_out.map(reversed)).transposed();
 
                 let mut cells = inp;
-                assert_eq!(cells.swipe_down(), inp != expected_out, "Input: {inp:?}");
+                assert_eq!(
+                    cells.swipe_down().is_changed(),
+                    inp != expected_out,
+                    "Input: {inp:?}"
+                );
                 assert_eq!(cells, expected_out, "Input: {inp:?}");
             }
         }
@@ -556,5 +758,160 @@ mod test_board {
         }
     }
 
+    #[test]
+    fn test_generic_spawns() {
+        setup();
+        for &cells in TEST_CASES.iter().flat_map(|(i, o)| [i, o]) {
+            let cells = Cells::from_cells(cells);
+            let mut fast_spawns = Spawns::new(cells);
+            let mut generic_spawns = Spawns::new(cells);
+
+            loop {
+                let fast = fast_spawns.fast_next();
+                let generic = generic_spawns.generic_next();
+
+                match (fast, generic) {
+                    (None, None) => break,
+                    (Some(fast), Some(generic)) => {
+                        assert_eq!(fast.value, generic.value, "Input:\n{cells}");
+                        assert_eq!(fast.weight.get(), generic.weight.get(), "Input:\n{cells}");
+                    }
+                    (fast, generic) => panic!(
+                        "Spawns ended at different times for:\n{cells}\nfast: {:?}\ngeneric: {:?}",
+                        fast.map(|weighted| weighted.value),
+                        generic.map(|weighted| weighted.value),
+                    ),
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn test_spawns_size_hint() {
+        setup();
+        for &cells in TEST_CASES.iter().flat_map(|(i, o)| [i, o]) {
+            let cells = Cells::from_cells(cells);
+            let mut spawns = Spawns::new(cells);
+            assert_eq!(spawns.len(), 2 * cells.count_empty(), "Input:\n{cells}");
+
+            let mut remaining = spawns.len();
+            while spawns.next().is_some() {
+                remaining -= 1;
+                assert_eq!(spawns.size_hint(), (remaining, Some(remaining)));
+            }
+
+            assert_eq!(remaining, 0, "Input:\n{cells}");
+        }
+
+        assert_eq!(Spawns::<4, 4>::empty().size_hint(), (0, Some(0)));
+    }
+
+    #[test]
+    fn test_spawns_other_sizes() {
+        setup();
+
+        let cells = Cells::from_cells([[1, 0, 2], [0, 0, 3], [4, 5, 0]]);
+        let spawns = Spawns::new(cells).collect_vec();
+        assert_eq!(spawns.len(), 8);
+        assert_eq!(spawns.iter().map(|w| w.weight.get()).sum::<u32>(), 12);
+        for weighted in &spawns {
+            let new_cells = weighted.value;
+            let changed = new_cells
+                .as_flattened()
+                .iter()
+                .zip(cells.as_flattened())
+                .filter(|(new, old)| new != old)
+                .collect_vec();
+
+            let &[(&spawned, &0)] = changed.as_slice() else {
+                panic!("Expected a single spawn on an empty cell:\n{new_cells}");
+            };
+
+            let expected_weight = if spawned == 1 { 2 } else { 1 };
+            assert_eq!(
+                weighted.weight.get(),
+                expected_weight,
+                "Spawn:\n{new_cells}"
+            );
+        }
+
+        assert_eq!(Spawns::new(Cells::<5, 5>::new()).count(), 50);
+        assert_eq!(Spawns::new(Cells::<2, 3>::new()).count(), 12);
+    }
+
+    #[test]
+    fn test_spawn_weights() {
+        setup();
+        let rules = SpawnRules::new().with_weights([1, 0, 3, 0]);
+        let cells = Cells::from_cells([[1, 0, 2], [0, 5, 3], [4, 1, 2]]);
+        let mut spawns = Spawns::with_rules(cells, rules);
+        assert_eq!(spawns.len(), 4);
+
+        let expected = [
+            ((0, 1), 3, 3),
+            ((1, 0), 3, 3),
+            ((0, 1), 1, 1),
+            ((1, 0), 1, 1),
+        ];
+        for ((i, j), exponent, weight) in expected {
+            let spawn = spawns.next().unwrap();
+            let mut expected_cells = cells;
+            expected_cells[i][j] = exponent;
+
+            assert_eq!(spawn.value, expected_cells);
+            assert_eq!(spawn.weight.get(), weight);
+        }
+
+        assert!(spawns.next().is_none());
+
+        // The fast path is only used for the default weights.
+        let cells = Cells::from_cells(TEST_CASES[2].0);
+        let spawns = Spawns::with_rules(cells, rules);
+        assert_eq!(spawns.len(), 2 * cells.count_empty());
+        assert!(spawns
+            .map(|spawn| spawn.value.as_flattened().iter().sum::<u8>())
+            .all(|sum| sum == cells.as_flattened().iter().sum::<u8>() + 1
+                || sum == cells.as_flattened().iter().sum::<u8>() + 3));
+    }
+
+    #[test]
+    fn test_multiple_spawns() {
+        setup();
+        let rules = SpawnRules::new().with_tiles_per_turn(2);
+
+        let cells = Cells::from_cells([[1, 0, 2, 3], [4, 5, 6, 7], [0, 2, 3, 4], [5, 6, 7, 0]]);
+        let spawns = Spawns::with_rules(cells, rules).collect_vec();
+
+        // Every pair of the 3 empty cells, with 2 exponents each.
+        assert_eq!(spawns.len(), 3 * 2 * 2);
+        let total_weight: u32 = spawns.iter().map(|spawn| spawn.weight.get()).sum();
+        assert_eq!(total_weight, 3 * (2 + 1) * (2 + 1));
+        assert!(spawns.iter().all(|spawn| spawn.value.count_empty() == 1));
+        assert_eq!(spawns.iter().map(|spawn| spawn.value).unique().count(), 12);
+
+        // Only one tile fits.
+        let cells = Cells::from_cells([[1, 0, 2, 3], [4, 5, 6, 7], [8, 2, 3, 4], [5, 6, 7, 1]]);
+        let spawns = Spawns::with_rules(cells, rules);
+        assert_eq!(spawns.len(), 2);
+        assert!(spawns
+            .into_iter()
+            .all(|spawn| spawn.value.count_empty() == 0));
+
+        // The heaviest rules still have weights that fit.
+        let rules = SpawnRules::new()
+            .with_weights([u8::MAX; 4])
+            .with_tiles_per_turn(SpawnRules::MAX_TILES_PER_TURN);
+        let total_weight: u64 = Spawns::with_rules(Cells::<4, 4>::default(), rules)
+            .map(|spawn| u64::from(spawn.weight.get()))
+            .sum();
+        assert_eq!(total_weight, 560 * (4 * 255u64).pow(3));
+    }
+
+    #[test]
+    #[should_panic = "at most 3 tiles"]
+    fn test_too_many_tiles_per_turn() {
+        let _ = SpawnRules::new().with_tiles_per_turn(SpawnRules::MAX_TILES_PER_TURN + 1);
+    }
+
     // TODO: Test count empty
 }
@@ -0,0 +1,226 @@
+use super::{Cell, Cells};
+use std::str::FromStr;
+
+/// Largest exponent the [`Display`](std::fmt::Display) format can show, written as `z`.
+pub const MAX_PARSED_CELL: Cell = 35;
+
+#[derive(thiserror::Error, Debug, Clone, PartialEq, Eq)]
+pub enum ParseCellsError {
+    #[error("expected {expected} rows but found {found}")]
+    RowCount { expected: usize, found: usize },
+
+    #[error("expected {expected} cells in row {row} but found {found}")]
+    ColumnCount {
+        row: usize,
+        expected: usize,
+        found: usize,
+    },
+
+    #[error("invalid cell {cell:?} in row {row}")]
+    InvalidCell { row: usize, cell: String },
+
+    #[error("tile {tile} in row {row} is not a power of two")]
+    NotPowerOfTwo { row: usize, tile: u64 },
+
+    #[error("tile {tile} in row {row} is larger than 2^{MAX_PARSED_CELL}")]
+    TileTooLarge { row: usize, tile: u64 },
+}
+
+impl<const COLS: usize, const ROWS: usize> Cells<COLS, ROWS> {
+    /// Parses a board written with the real tile values (`2`, `4`, `8` ... `65536`), empty cells
+    /// are written as `.` or `0`.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the dimensions don't match or a tile is not a power of two up to
+    /// 2^[`MAX_PARSED_CELL`].
+    pub fn from_tiles(s: &str) -> Result<Self, ParseCellsError> {
+        parse_grid(s, |row, cell| {
+            if cell == "." {
+                return Ok(0);
+            }
+
+            let tile: u64 = cell.parse().map_err(|_| ParseCellsError::InvalidCell {
+                row,
+                cell: cell.to_owned(),
+            })?;
+
+            match tile {
+                0 => Ok(0),
+                2.. if tile.is_power_of_two() => match tile.trailing_zeros() as Cell {
+                    exponent @ ..=MAX_PARSED_CELL => Ok(exponent),
+                    _ => Err(ParseCellsError::TileTooLarge { row, tile }),
+                },
+                _ => Err(ParseCellsError::NotPowerOfTwo { row, tile }),
+            }
+        })
+    }
+}
+
+/// Parses the format used by [`Display`](std::fmt::Display): one row per line with the
+/// exponents written as `.`, `1`-`9` and `a`-`z`, separated by whitespace.
+impl<const COLS: usize, const ROWS: usize> FromStr for Cells<COLS, ROWS> {
+    type Err = ParseCellsError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        parse_grid(s, |row, cell| {
+            let invalid_cell = || ParseCellsError::InvalidCell {
+                row,
+                cell: cell.to_owned(),
+            };
+
+            let &[byte] = cell.as_bytes() else {
+                return Err(invalid_cell());
+            };
+
+            match byte {
+                b'.' => Ok(0),
+                b'1'..=b'9' => Ok(byte - b'0'),
+                b'a'..=b'z' => Ok(byte - b'a' + 10),
+                _ => Err(invalid_cell()),
+            }
+        })
+    }
+}
+
+fn parse_grid<const COLS: usize, const ROWS: usize>(
+    s: &str,
+    parse_cell: impl Fn(usize, &str) -> Result<Cell, ParseCellsError>,
+) -> Result<Cells<COLS, ROWS>, ParseCellsError> {
+    let lines: Vec<_> = s.lines().filter(|line| !line.trim().is_empty()).collect();
+    if lines.len() != ROWS {
+        return Err(ParseCellsError::RowCount {
+            expected: ROWS,
+            found: lines.len(),
+        });
+    }
+
+    let mut cells = Cells::new();
+    for (row, line) in lines.into_iter().enumerate() {
+        let tokens: Vec<_> = line.split_whitespace().collect();
+        if tokens.len() != COLS {
+            return Err(ParseCellsError::ColumnCount {
+                row,
+                expected: COLS,
+                found: tokens.len(),
+            });
+        }
+
+        for (cell, token) in cells[row].iter_mut().zip(tokens) {
+            *cell = parse_cell(row, token)?;
+        }
+    }
+
+    Ok(cells)
+}
+
+/// Creates [`Cells`] from rows written in the [`Display`](std::fmt::Display) format.
+///
+/// ```
+/// use rust_2048_solver::{cells, game::twenty_forty_eight::board::Cells};
+///
+/// let cells: Cells<4, 2> = cells![
+///     "1 . . 2",
+///     ". a . .",
+/// ];
+/// assert_eq!(cells, Cells::from_cells([[1, 0, 0, 2], [0, 10, 0, 0]]));
+/// ```
+///
+/// # Panics
+///
+/// Panics if the rows can't be parsed into the inferred board size.
+#[macro_export]
+macro_rules! cells {
+    ($($row:literal),+ $(,)?) => {
+        <$crate::game::twenty_forty_eight::board::Cells<_, _> as ::std::str::FromStr>::from_str(
+            ::std::concat!($($row, "\n"),+),
+        )
+        .expect("the rows should form a valid board")
+    };
+}
+
+#[cfg(test)]
+mod tests {
+    use super::ParseCellsError;
+    use crate::game::twenty_forty_eight::{board::Cells, State};
+
+    const CELLS: [[u8; 4]; 4] = [[0, 1, 2, 9], [10, 11, 0, 35], [0, 0, 0, 0], [3, 4, 5, 6]];
+
+    #[test]
+    fn test_display_round_trip() {
+        let cells = Cells::from_cells(CELLS);
+        assert_eq!(cells.to_string().parse(), Ok(cells));
+
+        let state: State<4, 4> = cells.to_string().parse().unwrap();
+        assert_eq!(state, State::from_cells(cells));
+
+        let cells: Cells<3, 2> = crate::cells!["1 . 2", "z y x"];
+        assert_eq!(cells, Cells::from_cells([[1, 0, 2], [35, 34, 33]]));
+    }
+
+    #[test]
+    fn test_from_tiles() {
+        let cells = Cells::<4, 2>::from_tiles("2 . 0 4\n1024 65536 8 16");
+        assert_eq!(cells, Ok(Cells::from_cells([[1, 0, 0, 2], [10, 16, 3, 4]])));
+
+        assert_eq!(
+            Cells::<2, 1>::from_tiles("2 6"),
+            Err(ParseCellsError::NotPowerOfTwo { row: 0, tile: 6 })
+        );
+        assert_eq!(
+            Cells::<2, 1>::from_tiles("1 2"),
+            Err(ParseCellsError::NotPowerOfTwo { row: 0, tile: 1 })
+        );
+        assert_eq!(
+            Cells::<2, 1>::from_tiles("2 34359738368"),
+            Ok(Cells::from_cells([[1, 35]]))
+        );
+        assert_eq!(
+            Cells::<2, 1>::from_tiles("2 68719476736"),
+            Err(ParseCellsError::TileTooLarge {
+                row: 0,
+                tile: 1 << 36
+            })
+        );
+        assert_eq!(
+            Cells::<2, 1>::from_tiles("2 four"),
+            Err(ParseCellsError::InvalidCell {
+                row: 0,
+                cell: "four".to_owned()
+            })
+        );
+    }
+
+    #[test]
+    fn test_parse_errors() {
+        assert_eq!(
+            "1 2\n3 4\n5 6".parse::<Cells<2, 2>>(),
+            Err(ParseCellsError::RowCount {
+                expected: 2,
+                found: 3
+            })
+        );
+        assert_eq!(
+            "1 2\n3 4 5".parse::<Cells<2, 2>>(),
+            Err(ParseCellsError::ColumnCount {
+                row: 1,
+                expected: 2,
+                found: 3
+            })
+        );
+        assert_eq!(
+            "1 2\n3 A".parse::<Cells<2, 2>>(),
+            Err(ParseCellsError::InvalidCell {
+                row: 1,
+                cell: "A".to_owned()
+            })
+        );
+        assert_eq!(
+            "1 2\n3 10".parse::<Cells<2, 2>>(),
+            Err(ParseCellsError::InvalidCell {
+                row: 1,
+                cell: "10".to_owned()
+            })
+        );
+    }
+}
@@ -0,0 +1,172 @@
+//! Swipes of a whole 4x4 board at once, with the 16 cells in a single `u8x16`.
+//!
+//! Every direction is reduced to swiping the 4 rows to the left by permuting the cells in-register
+//! before and after the swipe.
+
+use super::fast_swipe::{merge_score, SwipeResult};
+use super::{Cells, Direction};
+use std::simd::{cmp::SimdPartialEq as _, simd_swizzle, u32x4, u8x16, Select as _, ToBytes as _};
+
+/// Reverses every row.
+const REVERSE: [usize; 16] = [3, 2, 1, 0, 7, 6, 5, 4, 11, 10, 9, 8, 15, 14, 13, 12];
+
+/// Swaps rows and columns, it is its own inverse.
+const TRANSPOSE: [usize; 16] = [0, 4, 8, 12, 1, 5, 9, 13, 2, 6, 10, 14, 3, 7, 11, 15];
+
+/// Rotates clockwise, so the bottom of each column is at the start of a row.
+const ROTATE_CLOCKWISE: [usize; 16] = [12, 8, 4, 0, 13, 9, 5, 1, 14, 10, 6, 2, 15, 11, 7, 3];
+
+/// Inverse of [`ROTATE_CLOCKWISE`].
+const ROTATE_COUNTER_CLOCKWISE: [usize; 16] =
+    [3, 7, 11, 15, 2, 6, 10, 14, 1, 5, 9, 13, 0, 4, 8, 12];
+
+impl Cells<4, 4> {
+    /// Swipes the whole board at once using SIMD, gives the same result as [`Cells::swipe`].
+    pub fn swipe_simd(&mut self, direction: Direction) -> SwipeResult {
+        let cells = self.as_simd();
+
+        let (swiped, score) = match direction {
+            Direction::Left => swipe_rows_left(cells),
+            Direction::Right => {
+                let (swiped, score) = swipe_rows_left(simd_swizzle!(cells, REVERSE));
+                (simd_swizzle!(swiped, REVERSE), score)
+            }
+            Direction::Up => {
+                let (swiped, score) = swipe_rows_left(simd_swizzle!(cells, TRANSPOSE));
+                (simd_swizzle!(swiped, TRANSPOSE), score)
+            }
+            Direction::Down => {
+                let (swiped, score) = swipe_rows_left(simd_swizzle!(cells, ROTATE_CLOCKWISE));
+                (simd_swizzle!(swiped, ROTATE_COUNTER_CLOCKWISE), score)
+            }
+        };
+
+        if swiped == cells {
+            return SwipeResult::Unchanged;
+        }
+
+        *self = Cells::from_simd(swiped);
+        SwipeResult::Changed { score }
+    }
+}
+
+/// Moves the occupied cells of every row to its start.
+///
+/// Same steps as [`swipe_left_4_fast`](super::fast_swipe::swipe_left_4_fast), with a select
+/// instead of each branch.
+fn compact(cells: u8x16) -> u8x16 {
+    let mut rows = u32x4::from_le_bytes(cells);
+    let is_empty =
+        |rows: u32x4, byte: u32| (rows & u32x4::splat(0xFF << (8 * byte))).simd_eq(u32x4::splat(0));
+
+    // Move block[3] to the left if block[2] is empty
+    let shifted = (rows >> 8) & u32x4::splat(!0xFFFF) | rows & u32x4::splat(0xFFFF);
+    rows = is_empty(rows, 2).select(shifted, rows);
+
+    // Move block[3, 2] to the left if block[1] is empty
+    let shifted = (rows >> 8) & u32x4::splat(!0xFF) | rows & u32x4::splat(0xFF);
+    rows = is_empty(rows, 1).select(shifted, rows);
+
+    // Move block[3, 2, 1] to the left if block[0] is empty
+    rows = is_empty(rows, 0).select(rows >> 8, rows);
+
+    u8x16::from_le_bytes(rows.to_le_bytes())
+}
+
+/// Swipes the 4 rows to the left, returns the swiped cells and the score of the merges.
+fn swipe_rows_left(cells: u8x16) -> (u8x16, u32) {
+    let cells = compact(cells);
+
+    // Shifting the little-endian rows by a byte moves every cell to its neighbour in the row.
+    let rows = u32x4::from_le_bytes(cells);
+    let next = u8x16::from_le_bytes((rows >> 8).to_le_bytes());
+    let previous = |mask: u8x16| u32x4::from_le_bytes(mask) << 8;
+
+    // A cell merges with the next one if they are equal, unless it was merged into the previous.
+    let equal = cells.simd_eq(next) & cells.simd_ne(u8x16::splat(0));
+    let equal = equal.select(u8x16::splat(0xFF), u8x16::splat(0));
+    let mut merged = equal;
+    for _ in 0..2 {
+        merged = equal & !u8x16::from_le_bytes(previous(merged).to_le_bytes());
+    }
+
+    let absorbed = u8x16::from_le_bytes(previous(merged).to_le_bytes());
+    let swiped = (cells + (merged & u8x16::splat(1))) & !absorbed;
+
+    let mut score = 0;
+    let mut merges = merged.simd_ne(u8x16::splat(0)).to_bitmask();
+    while merges != 0 {
+        let i = merges.trailing_zeros() as usize;
+        score += merge_score(swiped[i]);
+        merges &= merges - 1;
+    }
+
+    (compact(swiped), score)
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::game::twenty_forty_eight::board::fast_swipe::{swipe_left_generic, SwipeResult};
+    use crate::game::twenty_forty_eight::board::{Cells, Direction};
+    use crate::game::Discrete as _;
+
+    /// Swipes every row or column on its own with the scalar implementation.
+    fn swipe_scalar(cells: Cells<4, 4>, direction: Direction) -> (Cells<4, 4>, SwipeResult) {
+        let (transpose, reverse) = match direction {
+            Direction::Left => (false, false),
+            Direction::Right => (false, true),
+            Direction::Up => (true, false),
+            Direction::Down => (true, true),
+        };
+
+        let mut lines = if transpose { cells.transposed() } else { cells };
+        let mut result = SwipeResult::Unchanged;
+        for line in lines.iter_mut() {
+            if reverse {
+                line.reverse();
+            }
+
+            result = result.combine(swipe_left_generic(line));
+
+            if reverse {
+                line.reverse();
+            }
+        }
+
+        let swiped = if transpose { lines.transposed() } else { lines };
+        (swiped, result)
+    }
+
+    #[test]
+    fn test_simd_matches_scalar() {
+        // Every row with exponents up to 17 (131072) appears in every row and column of a board.
+        const MAX_EXPONENT: u32 = 17;
+        const ROW_COUNT: u32 = (MAX_EXPONENT + 1).pow(4);
+
+        let row = |n: u32| -> [u8; 4] {
+            std::array::from_fn(|i| {
+                (n % ROW_COUNT / (MAX_EXPONENT + 1).pow(i as u32) % (MAX_EXPONENT + 1)) as u8
+            })
+        };
+
+        for n in 0..ROW_COUNT {
+            let cells = Cells::from_cells([row(n), row(n + 1), row(n + 2), row(n + 3)]);
+
+            for (cells, direction) in [cells, cells.transposed()]
+                .into_iter()
+                .flat_map(|cells| Direction::iter().map(move |direction| (cells, direction)))
+            {
+                let (expected, expected_result) = swipe_scalar(cells, direction);
+
+                let mut swiped = cells;
+                let result = swiped.swipe_simd(direction);
+
+                assert_eq!(swiped, expected, "Input:\n{cells}\nDirection: {direction}");
+                assert_eq!(
+                    result, expected_result,
+                    "Input:\n{cells}\nDirection: {direction}"
+                );
+            }
+        }
+    }
+}
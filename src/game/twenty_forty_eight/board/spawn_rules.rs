@@ -0,0 +1,112 @@
+use super::Cell;
+
+/// How new tiles are spawned on the board.
+#[derive(Copy, Clone, Debug, PartialEq, Eq, Hash, PartialOrd, Ord)]
+pub struct SpawnRules {
+    /// Relative chance of spawning each exponent, `weights[i]` is the weight of exponent `i + 1`.
+    pub weights: [u8; SpawnRules::MAX_EXPONENT as usize],
+
+    /// Number of tiles on the board at the start of the game.
+    pub initial_tiles: u8,
+
+    /// Number of tiles spawned after each move (or less if there is not enough room).
+    pub tiles_per_turn: u8,
+}
+
+impl SpawnRules {
+    /// Largest exponent that can be spawned.
+    pub const MAX_EXPONENT: Cell = 4;
+
+    /// Most tiles spawned per turn. The spawns of a turn are enumerated at every chance node, and
+    /// their weight is the product of the weights of the tiles, which must fit in a `u32`.
+    pub const MAX_TILES_PER_TURN: u8 = 3;
+
+    /// 2s with weight 2 and 4s with weight 1, one tile at the start and one after each move.
+    pub const fn new() -> Self {
+        Self {
+            weights: [2, 1, 0, 0],
+            initial_tiles: 1,
+            tiles_per_turn: 1,
+        }
+    }
+
+    /// Same as [`SpawnRules::new`] but starts with two tiles, like the original game.
+    pub const fn official() -> Self {
+        Self::new().with_initial_tiles(2)
+    }
+
+    /// # Panics
+    ///
+    /// Panics if all the weights are zero.
+    #[must_use]
+    pub fn with_weights(mut self, weights: [u8; Self::MAX_EXPONENT as usize]) -> Self {
+        assert!(
+            weights.iter().any(|&weight| weight != 0),
+            "at least one exponent should have a non-zero weight"
+        );
+
+        self.weights = weights;
+        self
+    }
+
+    #[must_use]
+    pub const fn with_initial_tiles(mut self, initial_tiles: u8) -> Self {
+        self.initial_tiles = initial_tiles;
+        self
+    }
+
+    /// # Panics
+    ///
+    /// Panics if `tiles_per_turn` is zero or more than [`SpawnRules::MAX_TILES_PER_TURN`].
+    #[must_use]
+    pub fn with_tiles_per_turn(mut self, tiles_per_turn: u8) -> Self {
+        assert_ne!(
+            tiles_per_turn, 0,
+            "at least one tile should spawn each turn"
+        );
+        assert!(
+            tiles_per_turn <= Self::MAX_TILES_PER_TURN,
+            "at most {} tiles can spawn each turn",
+            Self::MAX_TILES_PER_TURN
+        );
+
+        self.tiles_per_turn = tiles_per_turn;
+        self
+    }
+
+    /// Weight of spawning `exponent`, `0` if it can't be spawned.
+    pub fn weight(&self, exponent: Cell) -> u8 {
+        usize::from(exponent)
+            .checked_sub(1)
+            .and_then(|i| self.weights.get(i))
+            .copied()
+            .unwrap_or(0)
+    }
+
+    /// Exponents that can be spawned from the highest to the lowest, with their weights.
+    pub fn exponents(&self) -> impl Iterator<Item = (Cell, u8)> + '_ {
+        (1..=Self::MAX_EXPONENT)
+            .rev()
+            .map(|exponent| (exponent, self.weight(exponent)))
+            .filter(|&(_, weight)| weight != 0)
+    }
+
+    /// The next exponent to spawn after `exponent`, or `0` if there is none.
+    pub(super) fn next_exponent(&self, exponent: Cell) -> Cell {
+        self.exponents()
+            .map(|(exponent, _weight)| exponent)
+            .find(|&next| next < exponent)
+            .unwrap_or(0)
+    }
+
+    /// Returns `true` if a single tile is spawned per turn with the default weights.
+    pub(super) fn is_default_spawn(&self) -> bool {
+        self.weights == Self::new().weights && self.tiles_per_turn == 1
+    }
+}
+
+impl Default for SpawnRules {
+    fn default() -> Self {
+        Self::new()
+    }
+}
@@ -0,0 +1,240 @@
+use super::{Cells, Direction};
+use std::array;
+
+/// One of the 8 symmetries of a square board (the dihedral group of order 8).
+///
+/// Every symmetry is a transpose (optional) followed by flipping the rows and/or the columns.
+#[derive(Copy, Clone, Debug, PartialEq, Eq, Hash)]
+pub enum Symmetry {
+    Identity,
+    RotateClockwise,
+    Rotate180,
+    RotateCounterClockwise,
+    FlipHorizontal,
+    FlipVertical,
+    Transpose,
+    AntiTranspose,
+}
+
+impl Symmetry {
+    pub const ALL: [Symmetry; 8] = [
+        Symmetry::Identity,
+        Symmetry::RotateClockwise,
+        Symmetry::Rotate180,
+        Symmetry::RotateCounterClockwise,
+        Symmetry::FlipHorizontal,
+        Symmetry::FlipVertical,
+        Symmetry::Transpose,
+        Symmetry::AntiTranspose,
+    ];
+
+    /// Returns `(transpose, flip_vertical, flip_horizontal)`, applied in that order.
+    const fn parts(self) -> (bool, bool, bool) {
+        match self {
+            Symmetry::Identity => (false, false, false),
+            Symmetry::FlipHorizontal => (false, false, true),
+            Symmetry::FlipVertical => (false, true, false),
+            Symmetry::Rotate180 => (false, true, true),
+            Symmetry::Transpose => (true, false, false),
+            Symmetry::RotateClockwise => (true, false, true),
+            Symmetry::RotateCounterClockwise => (true, true, false),
+            Symmetry::AntiTranspose => (true, true, true),
+        }
+    }
+
+    /// Returns `true` if the symmetry swaps the rows and columns of the board.
+    pub const fn transposes(self) -> bool {
+        self.parts().0
+    }
+
+    /// Returns the symmetry that undoes this one.
+    pub const fn inverse(self) -> Self {
+        match self {
+            Symmetry::RotateClockwise => Symmetry::RotateCounterClockwise,
+            Symmetry::RotateCounterClockwise => Symmetry::RotateClockwise,
+            other => other,
+        }
+    }
+
+    /// Maps an action on the original board to the same action on the transformed board.
+    pub fn transform_direction(self, direction: Direction) -> Direction {
+        let (transpose, flip_vertical, flip_horizontal) = self.parts();
+
+        let direction = match (transpose, direction) {
+            (false, direction) => direction,
+            (true, Direction::Up) => Direction::Left,
+            (true, Direction::Down) => Direction::Right,
+            (true, Direction::Left) => Direction::Up,
+            (true, Direction::Right) => Direction::Down,
+        };
+
+        match direction {
+            Direction::Up if flip_vertical => Direction::Down,
+            Direction::Down if flip_vertical => Direction::Up,
+            Direction::Left if flip_horizontal => Direction::Right,
+            Direction::Right if flip_horizontal => Direction::Left,
+            direction => direction,
+        }
+    }
+}
+
+impl crate::game::Discrete for Symmetry {
+    fn iter() -> impl Iterator<Item = Self> {
+        Self::ALL.into_iter()
+    }
+}
+
+impl<const COLS: usize, const ROWS: usize> Cells<COLS, ROWS> {
+    /// Mirrors the board left to right.
+    pub fn flipped_horizontally(mut self) -> Self {
+        self.iter_mut().for_each(|row| row.reverse());
+        self
+    }
+
+    /// Mirrors the board top to bottom.
+    pub fn flipped_vertically(mut self) -> Self {
+        self.reverse();
+        self
+    }
+
+    /// Rotates the board by 90 degrees clockwise.
+    pub fn rotated_clockwise(self) -> Cells<ROWS, COLS> {
+        self.transposed().flipped_horizontally()
+    }
+
+    /// Rotates the board by 90 degrees counter-clockwise.
+    pub fn rotated_counter_clockwise(self) -> Cells<ROWS, COLS> {
+        self.transposed().flipped_vertically()
+    }
+
+    /// Applies the symmetry to the board, returns `None` if the symmetry swaps rows and columns of
+    /// a board that is not square.
+    pub fn transformed(self, symmetry: Symmetry) -> Option<Self> {
+        let (transpose, flip_vertical, flip_horizontal) = symmetry.parts();
+
+        let mut cells = match transpose {
+            false => self,
+            true if COLS == ROWS => {
+                Cells::from_cells(array::from_fn(|i| array::from_fn(|j| self[j][i])))
+            }
+            true => return None,
+        };
+
+        if flip_vertical {
+            cells = cells.flipped_vertically();
+        }
+
+        if flip_horizontal {
+            cells = cells.flipped_horizontally();
+        }
+
+        Some(cells)
+    }
+
+    /// Returns the smallest of all the symmetric boards and the symmetry that produces it.
+    ///
+    /// Symmetric boards have the same canonical form, so it can be used as a key for positions
+    /// that have the same value.
+    pub fn canonical(self) -> (Self, Symmetry) {
+        Symmetry::ALL
+            .into_iter()
+            .filter_map(|symmetry| Some((self.transformed(symmetry)?, symmetry)))
+            .min_by_key(|(cells, _symmetry)| *cells)
+            .expect("identity is always a valid symmetry")
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::Symmetry;
+    use crate::game::twenty_forty_eight::board::{Cells, Direction};
+    use crate::game::Discrete as _;
+
+    const CELLS: [[u8; 4]; 4] = [[1, 2, 3, 4], [5, 6, 7, 8], [9, 10, 11, 12], [13, 14, 15, 0]];
+
+    #[test]
+    fn test_transforms() {
+        let cells = Cells::from_cells(CELLS);
+        let transform = |symmetry| cells.transformed(symmetry).unwrap();
+
+        assert_eq!(transform(Symmetry::Identity), cells);
+        assert_eq!(transform(Symmetry::Transpose), cells.transposed());
+        assert_eq!(
+            transform(Symmetry::RotateClockwise),
+            cells.rotated_clockwise()
+        );
+        assert_eq!(
+            transform(Symmetry::RotateCounterClockwise),
+            cells.rotated_counter_clockwise()
+        );
+        assert_eq!(
+            transform(Symmetry::Rotate180),
+            cells.rotated_clockwise().rotated_clockwise()
+        );
+        assert_eq!(
+            transform(Symmetry::AntiTranspose),
+            cells
+                .rotated_clockwise()
+                .transposed()
+                .rotated_counter_clockwise()
+        );
+        assert_eq!(transform(Symmetry::RotateClockwise)[0], [13, 9, 5, 1]);
+
+        for symmetry in Symmetry::iter() {
+            let restored = transform(symmetry).transformed(symmetry.inverse());
+            assert_eq!(restored, Some(cells), "Symmetry: {symmetry:?}");
+        }
+
+        let unique: std::collections::HashSet<_> = Symmetry::iter().map(transform).collect();
+        assert_eq!(unique.len(), 8);
+    }
+
+    #[test]
+    fn test_canonical() {
+        let cells = Cells::from_cells(CELLS);
+        let (canonical, symmetry) = cells.canonical();
+        assert_eq!(cells.transformed(symmetry), Some(canonical));
+
+        for symmetry in Symmetry::iter() {
+            let transformed = cells.transformed(symmetry).unwrap();
+            assert_eq!(
+                transformed.canonical().0,
+                canonical,
+                "Symmetry: {symmetry:?}"
+            );
+        }
+
+        let cells = Cells::from_cells([[1, 2, 3], [4, 5, 6]]);
+        assert_eq!(cells.transformed(Symmetry::Transpose), None);
+        assert_eq!(
+            cells.canonical().0,
+            Cells::from_cells([[1, 2, 3], [4, 5, 6]])
+        );
+        assert_eq!(
+            cells.flipped_vertically().canonical(),
+            (cells, Symmetry::FlipVertical)
+        );
+    }
+
+    #[test]
+    fn test_transform_direction() {
+        let cells = Cells::from_cells(CELLS);
+        for symmetry in Symmetry::iter() {
+            for direction in Direction::iter() {
+                let swiped_then_transformed = cells
+                    .swiped(direction)
+                    .and_then(|(cells, _score)| cells.transformed(symmetry));
+
+                let transformed = cells.transformed(symmetry).unwrap();
+                let transformed_then_swiped = transformed
+                    .swiped(symmetry.transform_direction(direction))
+                    .map(|(cells, _score)| cells);
+
+                assert_eq!(
+                    swiped_then_transformed, transformed_then_swiped,
+                    "Symmetry: {symmetry:?}, Direction: {direction}"
+                );
+            }
+        }
+    }
+}
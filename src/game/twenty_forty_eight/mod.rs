@@ -1,29 +1,47 @@
+pub mod bitboard;
 pub mod board;
+pub mod record;
 
+use super::{IllegalAction, OutcomeResult};
 use crate::accumulator::fraction::Weighted;
-use board::{Cells, Direction};
-use rand::distr::{
-    weighted::{Error as WeightedError, WeightedIndex},
-    Distribution as _,
-};
+use board::{Cells, Direction, SpawnRules};
+use rand::distr::{weighted::WeightedIndex, Distribution as _};
 use std::fmt::{self, Debug, Display};
+use std::str::FromStr;
 
 #[derive(Clone, Debug, Default, PartialEq, Eq, Hash)]
 pub struct State<const COLS: usize, const ROWS: usize> {
     pub cells: Cells<COLS, ROWS>,
+    pub rules: SpawnRules,
 }
 
 impl<const COLS: usize, const ROWS: usize> State<COLS, ROWS> {
     pub fn new() -> Self {
-        let cells = Cells::new();
-        // PERF: Don't generate all the possible states beforehand
-        let options: Vec<_> = board::Spawns::new(cells).collect();
-        let weights = options.iter().map(|weighted| weighted.weight.get());
-        let dist = WeightedIndex::new(weights).unwrap();
-        let mut rng = rand::rng();
-        let index = dist.sample(&mut rng);
+        Self::new_with_rng(&mut rand::rng())
+    }
+
+    pub fn new_with_rng<R: rand::Rng + ?Sized>(rng: &mut R) -> Self {
+        Self::new_with_rules(SpawnRules::default(), rng)
+    }
+
+    /// Starts a game with `rules.initial_tiles` tiles, spawned one at a time.
+    pub fn new_with_rules<R: rand::Rng + ?Sized>(rules: SpawnRules, rng: &mut R) -> Self {
+        let single_spawn = rules.with_tiles_per_turn(1);
+        let mut cells = Cells::new();
 
-        Self::from_cells(options[index].value)
+        for _ in 0..rules.initial_tiles {
+            // PERF: Don't generate all the possible states beforehand
+            let options: Vec<_> = board::Spawns::with_rules(cells, single_spawn).collect();
+            let weights = options.iter().map(|weighted| weighted.weight.get());
+            let Ok(dist) = WeightedIndex::new(weights) else {
+                // The board is full.
+                break;
+            };
+
+            cells = options[dist.sample(rng)].value;
+        }
+
+        Self { cells, rules }
     }
 
     pub fn from_cells<C>(cells: C) -> Self
@@ -32,8 +50,15 @@ impl<const COLS: usize, const ROWS: usize> State<COLS, ROWS> {
     {
         Self {
             cells: Cells::from(cells),
+            rules: SpawnRules::default(),
         }
     }
+
+    #[must_use]
+    pub fn with_rules(mut self, rules: SpawnRules) -> Self {
+        self.rules = rules;
+        self
+    }
 }
 
 impl<const ROWS: usize, const COLS: usize> super::GameState for State<COLS, ROWS> {
@@ -41,22 +66,20 @@ impl<const ROWS: usize, const COLS: usize> super::GameState for State<COLS, ROWS
     type Action = Direction;
     type Reward = f32;
 
-    fn outcome(self, action: Self::Action) -> (Self::Reward, Self::Outcome) {
-        let mut cells = self.cells;
-        let mut reward = 1.0; // TODO: Calculate the actual reward.
-
-        if !cells.swipe(action) {
-            // This action didn't change the board, so is not a valid action.
-            cells = board::Cells::new();
-            reward = 0.0;
-        }
+    fn outcome(self, action: Self::Action) -> OutcomeResult<Self> {
+        let (cells, score) = self.cells.swiped(action).ok_or(IllegalAction { action })?;
 
-        (reward, Outcome { cells })
+        let rules = self.rules;
+        Ok((score as f32, Outcome { cells, rules }))
     }
 
     fn is_terminal(&self) -> bool {
         self.cells.is_lost()
     }
+
+    fn is_legal(&self, action: &Self::Action) -> bool {
+        self.cells.can_swipe(*action)
+    }
 }
 
 impl<const ROWS: usize, const COLS: usize> Display for State<COLS, ROWS> {
@@ -65,9 +88,19 @@ impl<const ROWS: usize, const COLS: usize> Display for State<COLS, ROWS> {
     }
 }
 
+/// Parses the cells in their [`Display`] format, using the default [`SpawnRules`].
+impl<const ROWS: usize, const COLS: usize> FromStr for State<COLS, ROWS> {
+    type Err = board::ParseCellsError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        s.parse::<Cells<COLS, ROWS>>().map(Self::from_cells)
+    }
+}
+
 #[derive(Debug, Clone, PartialEq, Eq, Hash, PartialOrd, Ord)]
 pub struct Outcome<const COLS: usize, const ROWS: usize> {
     pub(crate) cells: Cells<COLS, ROWS>,
+    pub(crate) rules: SpawnRules,
 }
 
 impl<const COLS: usize, const ROWS: usize> Display for Outcome<COLS, ROWS> {
@@ -76,21 +109,41 @@ impl<const COLS: usize, const ROWS: usize> Display for Outcome<COLS, ROWS> {
     }
 }
 
+/// The states an [`Outcome`] can collapse to.
+#[derive(Debug)]
+pub struct States<const COLS: usize, const ROWS: usize>(board::Spawns<COLS, ROWS>);
+
+impl<const COLS: usize, const ROWS: usize> Iterator for States<COLS, ROWS> {
+    type Item = Weighted<State<COLS, ROWS>, board::Weight>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        let rules = self.0.rules();
+        let weighted = self.0.next()?;
+        Some(weighted.map(|cells| State { cells, rules }))
+    }
+
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        self.0.size_hint()
+    }
+}
+
+impl<const COLS: usize, const ROWS: usize> ExactSizeIterator for States<COLS, ROWS> {}
+
 impl<const COLS: usize, const ROWS: usize> IntoIterator for Outcome<COLS, ROWS> {
     type Item = Weighted<State<COLS, ROWS>, board::Weight>;
-    type IntoIter = std::iter::Map<
-        board::Spawns<COLS, ROWS>,
-        fn(<board::Spawns<COLS, ROWS> as Iterator>::Item) -> Self::Item,
-    >;
+    type IntoIter = States<COLS, ROWS>;
 
     fn into_iter(self) -> Self::IntoIter {
-        let spawns = if self.cells == Cells::new() {
-            board::Spawns::empty()
-        } else {
-            board::Spawns::new(self.cells)
-        };
+        States(board::Spawns::with_rules(self.cells, self.rules))
+    }
+}
 
-        spawns.map(|weighted| weighted.map(State::from_cells))
+impl<const COLS: usize, const ROWS: usize> super::Canonical for Outcome<COLS, ROWS> {
+    fn canonical(&self) -> Self {
+        Outcome {
+            cells: self.cells.canonical().0,
+            rules: self.rules,
+        }
     }
 }
 
@@ -102,7 +155,7 @@ impl<const COLS: usize, const ROWS: usize> super::DiscreteDistribution for Outco
 impl<const COLS: usize, const ROWS: usize> super::Outcome<State<COLS, ROWS>>
     for Outcome<COLS, ROWS>
 {
-    fn collapse(self) -> State<COLS, ROWS> {
+    fn collapse_with<R: rand::Rng + ?Sized>(self, rng: &mut R) -> State<COLS, ROWS> {
         let into_iter = self.clone().into_iter();
         let (min, _max) = into_iter.size_hint();
         let mut weights = Vec::with_capacity(min);
@@ -115,14 +168,131 @@ impl<const COLS: usize, const ROWS: usize> super::Outcome<State<COLS, ROWS>>
 
         match WeightedIndex::new(weights) {
             Ok(weighted_index) => {
-                let idx = weighted_index.sample(&mut rand::rng());
+                let idx = weighted_index.sample(rng);
                 items.swap_remove(idx)
             }
-            Err(WeightedError::InvalidWeight) => State::from_cells(Cells::new()),
             Err(err) => panic!(
                 "Failed to collapse outcome: {err}\noutcome:\n{}",
-                &self.cells
+                self.cells
             ),
         }
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::{Outcome, State};
+    use crate::game::{Discrete, GameState, Outcome as _};
+
+    fn play_until_lost<const COLS: usize, const ROWS: usize>(mut state: State<COLS, ROWS>) {
+        while !state.is_terminal() {
+            let action = state
+                .legal_actions()
+                .next()
+                .expect("a state that is not terminal should have a legal action");
+
+            let (_reward, outcome) = state.outcome(action).unwrap();
+            state = outcome.collapse();
+        }
+    }
+
+    #[test]
+    fn test_seeded_games() {
+        use rand::SeedableRng as _;
+
+        let play = |seed| {
+            let mut rng = rand::rngs::StdRng::seed_from_u64(seed);
+            let mut states = vec![State::<4, 4>::new_with_rng(&mut rng)];
+
+            let actions = (0..10).flat_map(|_| <State<4, 4> as GameState>::Action::iter());
+            for action in actions {
+                let state = states.last().unwrap().clone();
+                if let Ok((_reward, outcome)) = state.outcome(action) {
+                    states.push(outcome.collapse_with(&mut rng));
+                }
+            }
+
+            states
+        };
+
+        assert_eq!(play(1234), play(1234));
+        assert_ne!(play(1234), play(4321));
+    }
+
+    #[test]
+    fn test_spawn_rules() {
+        use super::SpawnRules;
+        use rand::SeedableRng as _;
+
+        let mut rng = rand::rngs::StdRng::seed_from_u64(0);
+        let rules = SpawnRules::official();
+        let state = State::<4, 4>::new_with_rules(rules, &mut rng);
+        assert_eq!(state.cells.count_empty(), 14);
+
+        let rules = SpawnRules::new()
+            .with_weights([0, 0, 1, 0])
+            .with_initial_tiles(3)
+            .with_tiles_per_turn(2);
+        let state = State::<4, 4>::new_with_rules(rules, &mut rng);
+        assert_eq!(state.cells.count_empty(), 13);
+        assert!(state
+            .cells
+            .as_flattened()
+            .iter()
+            .all(|&cell| cell == 0 || cell == 3));
+
+        let (swiped, _score) = <State<4, 4> as GameState>::Action::iter()
+            .find_map(|action| state.cells.swiped(action))
+            .unwrap();
+        let next = Outcome {
+            cells: swiped,
+            rules,
+        }
+        .collapse_with(&mut rng);
+        assert_eq!(next.rules, rules);
+        assert_eq!(next.cells.count_empty(), swiped.count_empty() - 2);
+    }
+
+    #[test]
+    fn test_legal_actions() {
+        use super::Direction;
+        use crate::game::IllegalAction;
+
+        let state =
+            State::<4, 4>::from_cells([[1, 2, 3, 4], [0, 0, 0, 5], [0, 0, 0, 6], [0, 0, 0, 7]]);
+        let legal: Vec<_> = state.legal_actions().collect();
+        assert_eq!(legal, [Direction::Down, Direction::Left]);
+
+        for action in Direction::iter() {
+            let outcome = state.clone().outcome(action);
+            assert_eq!(state.is_legal(&action), outcome.is_ok(), "Action: {action}");
+            assert_eq!(
+                state.cells.can_swipe(action),
+                state.cells.swiped(action).is_some(),
+                "Action: {action}"
+            );
+        }
+
+        assert_eq!(
+            state.outcome(Direction::Up).unwrap_err(),
+            IllegalAction {
+                action: Direction::Up
+            }
+        );
+
+        let state = State::<2, 2>::from_cells([[1, 2], [2, 1]]);
+        assert!(state.is_terminal());
+        assert_eq!(state.legal_actions().next(), None);
+    }
+
+    #[test]
+    fn test_other_sizes() {
+        let state = State::<3, 3>::new();
+        assert_eq!(state.cells.count_empty(), 8);
+        play_until_lost(state);
+
+        let state = State::<5, 5>::new();
+        assert_eq!(state.cells.count_empty(), 24);
+        play_until_lost(state);
+    }
+}
@@ -0,0 +1,624 @@
+//! A line-oriented text format for recording games.
+//!
+//! ```text
+//! size 4 4
+//! seed 1234
+//! rules 2 1 0 0 1 1
+//! board . . . . / . . 1 . / . . . . / . . . .
+//! turn left 3 0 1 0
+//! turn down 0 2 2 4
+//! turn up 3 1 1 0 0 2 8
+//! ```
+//!
+//! The header gives the board size (columns then rows), the seed of the random number generator,
+//! the [`SpawnRules`] (the weights of exponents 1 to 4, the initial tiles and the tiles per turn)
+//! and the initial board (rows in the [`Display`] format of [`Cells`], separated by `/`). Each
+//! turn is the action, the row, column and exponent of every spawned tile, and the reward of the
+//! action. Blank lines and lines starting with `#` are ignored.
+
+use super::board::{Cell, Cells, Direction, ParseCellsError, SpawnRules, Spawns};
+use super::{Outcome, State};
+use crate::game::{GameState as _, IllegalAction};
+use std::fmt::{self, Display};
+use std::io::{self, BufRead, Write};
+use std::str::FromStr;
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct SpawnedTile {
+    pub row: usize,
+    pub col: usize,
+    pub exponent: Cell,
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub struct Turn {
+    pub action: Direction,
+    /// Tiles spawned after the action, by row then column.
+    pub spawns: Vec<SpawnedTile>,
+    pub reward: f32,
+}
+
+impl Turn {
+    /// Returns the turn that took `outcome` to `next`, or `None` if `next` isn't `outcome` with
+    /// spawned tiles.
+    pub fn from_transition<const COLS: usize, const ROWS: usize>(
+        action: Direction,
+        reward: f32,
+        outcome: &Outcome<COLS, ROWS>,
+        next: &State<COLS, ROWS>,
+    ) -> Option<Self> {
+        let spawns: Vec<_> = (0..ROWS)
+            .flat_map(|row| (0..COLS).map(move |col| (row, col)))
+            .filter(|&(row, col)| outcome.cells[row][col] != next.cells[row][col])
+            .map(|(row, col)| SpawnedTile {
+                row,
+                col,
+                exponent: next.cells[row][col],
+            })
+            .collect();
+
+        let is_spawn = |spawn: &SpawnedTile| outcome.cells[spawn.row][spawn.col] == 0;
+        if spawns.is_empty() || !spawns.iter().all(is_spawn) {
+            return None;
+        }
+
+        Some(Self {
+            action,
+            spawns,
+            reward,
+        })
+    }
+}
+
+impl Display for Turn {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "turn {}", direction_name(self.action))?;
+        for SpawnedTile { row, col, exponent } in &self.spawns {
+            write!(f, " {row} {col} {exponent}")?;
+        }
+
+        write!(f, " {}", self.reward)
+    }
+}
+
+fn direction_name(direction: Direction) -> &'static str {
+    match direction {
+        Direction::Up => "up",
+        Direction::Down => "down",
+        Direction::Left => "left",
+        Direction::Right => "right",
+    }
+}
+
+fn parse_direction(s: &str) -> Option<Direction> {
+    match s {
+        "up" => Some(Direction::Up),
+        "down" => Some(Direction::Down),
+        "left" => Some(Direction::Left),
+        "right" => Some(Direction::Right),
+        _ => None,
+    }
+}
+
+#[derive(thiserror::Error, Debug)]
+pub enum ParseRecordError {
+    #[error(transparent)]
+    Io(#[from] io::Error),
+
+    #[error("line {line}: expected `{field}`")]
+    MissingField { line: usize, field: &'static str },
+
+    #[error("the record ended before `{field}`")]
+    UnexpectedEnd { field: &'static str },
+
+    #[error("line {line}: invalid `{field}` value {value:?}")]
+    InvalidValue {
+        line: usize,
+        field: &'static str,
+        value: String,
+    },
+
+    #[error("the record is for a {cols}x{rows} board")]
+    SizeMismatch { cols: usize, rows: usize },
+
+    #[error("line {line}: {source}")]
+    Board {
+        line: usize,
+        source: ParseCellsError,
+    },
+}
+
+#[derive(thiserror::Error, Debug, PartialEq)]
+pub enum ReplayError {
+    #[error("turn {turn}: {source}")]
+    IllegalAction {
+        turn: usize,
+        source: IllegalAction<Direction>,
+    },
+
+    #[error("turn {turn}: recorded reward {recorded} but the action is worth {actual}")]
+    RewardMismatch {
+        turn: usize,
+        recorded: f32,
+        actual: f32,
+    },
+
+    #[error("turn {turn}: the spawned tiles are not legal")]
+    IllegalSpawn { turn: usize },
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub struct GameRecord<const COLS: usize, const ROWS: usize> {
+    pub seed: u64,
+    pub rules: SpawnRules,
+    pub initial: Cells<COLS, ROWS>,
+    pub turns: Vec<Turn>,
+}
+
+impl<const COLS: usize, const ROWS: usize> GameRecord<COLS, ROWS> {
+    pub fn new(seed: u64, initial: &State<COLS, ROWS>) -> Self {
+        Self {
+            seed,
+            rules: initial.rules,
+            initial: initial.cells,
+            turns: Vec::new(),
+        }
+    }
+
+    /// # Errors
+    ///
+    /// Returns an error if the record can't be read or is malformed.
+    pub fn read(reader: impl BufRead) -> Result<Self, ParseRecordError> {
+        let mut lines = Vec::new();
+        for (i, line) in reader.lines().enumerate() {
+            let line = line?;
+            let trimmed = line.trim();
+            if !trimmed.is_empty() && !trimmed.starts_with('#') {
+                lines.push((i + 1, trimmed.to_owned()));
+            }
+        }
+
+        let mut lines = lines.into_iter();
+        let mut field = |field: &'static str| {
+            let (line, text) = lines
+                .next()
+                .ok_or(ParseRecordError::UnexpectedEnd { field })?;
+
+            match text.split_once(' ') {
+                Some((name, value)) if name == field => Ok((line, value.trim().to_owned())),
+                _ => Err(ParseRecordError::MissingField { line, field }),
+            }
+        };
+
+        let (line, size) = field("size")?;
+        if size.split_whitespace().collect::<Vec<_>>() != [COLS, ROWS].map(|n| n.to_string()) {
+            return Err(match parse_size(&size) {
+                Some((cols, rows)) => ParseRecordError::SizeMismatch { cols, rows },
+                None => invalid_value(line, "size", &size),
+            });
+        }
+
+        let (line, seed) = field("seed")?;
+        let seed = seed
+            .parse()
+            .map_err(|_| invalid_value(line, "seed", &seed))?;
+
+        let (line, rules) = field("rules")?;
+        let rules = parse_rules(&rules).ok_or_else(|| invalid_value(line, "rules", &rules))?;
+
+        let (line, board) = field("board")?;
+        let initial = board
+            .replace('/', "\n")
+            .parse()
+            .map_err(|source| ParseRecordError::Board { line, source })?;
+
+        let turns = lines
+            .map(|(line, text)| match text.split_once(' ') {
+                Some(("turn", turn)) => {
+                    parse_turn(turn).ok_or_else(|| invalid_value(line, "turn", turn))
+                }
+                _ => Err(ParseRecordError::MissingField {
+                    line,
+                    field: "turn",
+                }),
+            })
+            .collect::<Result<_, _>>()?;
+
+        Ok(Self {
+            seed,
+            rules,
+            initial,
+            turns,
+        })
+    }
+
+    /// # Errors
+    ///
+    /// Returns an error if writing fails.
+    pub fn write(&self, mut writer: impl Write) -> io::Result<()> {
+        write_header(&mut writer, self.seed, self.rules, &self.initial)?;
+        for turn in &self.turns {
+            writeln!(writer, "{turn}")?;
+        }
+
+        Ok(())
+    }
+
+    /// Plays the recorded turns from the initial board and returns the final state.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error on the first turn with an illegal action, a wrong reward or a spawn that
+    /// [`Spawns`] can't produce.
+    pub fn replay(&self) -> Result<State<COLS, ROWS>, ReplayError> {
+        let mut state = State::from_cells(self.initial).with_rules(self.rules);
+
+        for (turn, recorded) in self.turns.iter().enumerate() {
+            let (reward, outcome) = state
+                .outcome(recorded.action)
+                .map_err(|source| ReplayError::IllegalAction { turn, source })?;
+
+            if reward != recorded.reward {
+                return Err(ReplayError::RewardMismatch {
+                    turn,
+                    recorded: recorded.reward,
+                    actual: reward,
+                });
+            }
+
+            let illegal_spawn = ReplayError::IllegalSpawn { turn };
+
+            let mut cells = outcome.cells;
+            for spawn in &recorded.spawns {
+                match cells
+                    .get_mut(spawn.row)
+                    .and_then(|row| row.get_mut(spawn.col))
+                {
+                    Some(cell) => *cell = spawn.exponent,
+                    None => return Err(illegal_spawn),
+                }
+            }
+
+            let is_legal = Spawns::with_rules(outcome.cells, outcome.rules)
+                .any(|weighted| weighted.value == cells);
+            if !is_legal {
+                return Err(illegal_spawn);
+            }
+
+            state = State {
+                cells,
+                rules: outcome.rules,
+            };
+        }
+
+        Ok(state)
+    }
+}
+
+/// Writes a record turn by turn, so it can be followed while the game is being played.
+#[derive(Debug)]
+pub struct RecordWriter<W: Write> {
+    writer: W,
+}
+
+impl<W: Write> RecordWriter<W> {
+    /// Writes the header of the record.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if writing fails.
+    pub fn new<const COLS: usize, const ROWS: usize>(
+        mut writer: W,
+        seed: u64,
+        initial: &State<COLS, ROWS>,
+    ) -> io::Result<Self> {
+        write_header(&mut writer, seed, initial.rules, &initial.cells)?;
+        writer.flush()?;
+
+        Ok(Self { writer })
+    }
+
+    /// Writes the turn that took `outcome` to `next`.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if writing fails or `next` isn't `outcome` with spawned tiles.
+    pub fn record<const COLS: usize, const ROWS: usize>(
+        &mut self,
+        action: Direction,
+        reward: f32,
+        outcome: &Outcome<COLS, ROWS>,
+        next: &State<COLS, ROWS>,
+    ) -> io::Result<()> {
+        let turn = Turn::from_transition(action, reward, outcome, next).ok_or_else(|| {
+            io::Error::new(
+                io::ErrorKind::InvalidInput,
+                "only turns that spawn tiles on empty cells can be recorded",
+            )
+        })?;
+
+        writeln!(self.writer, "{turn}")?;
+        self.writer.flush()
+    }
+
+    pub fn into_inner(self) -> W {
+        self.writer
+    }
+}
+
+fn write_header<const COLS: usize, const ROWS: usize>(
+    writer: &mut impl Write,
+    seed: u64,
+    rules: SpawnRules,
+    initial: &Cells<COLS, ROWS>,
+) -> io::Result<()> {
+    let board = initial.to_string();
+    let rows: Vec<_> = board.lines().map(str::trim_end).collect();
+    let [w1, w2, w3, w4] = rules.weights;
+
+    writeln!(writer, "size {COLS} {ROWS}")?;
+    writeln!(writer, "seed {seed}")?;
+    writeln!(
+        writer,
+        "rules {w1} {w2} {w3} {w4} {} {}",
+        rules.initial_tiles, rules.tiles_per_turn
+    )?;
+    writeln!(writer, "board {}", rows.join(" / "))
+}
+
+fn invalid_value(line: usize, field: &'static str, value: &str) -> ParseRecordError {
+    ParseRecordError::InvalidValue {
+        line,
+        field,
+        value: value.to_owned(),
+    }
+}
+
+fn parse_size(s: &str) -> Option<(usize, usize)> {
+    let (cols, rows) = s.split_once(' ')?;
+    Some((cols.trim().parse().ok()?, rows.trim().parse().ok()?))
+}
+
+/// Parses rules that [`SpawnRules`] would accept, without panicking on the others.
+fn parse_rules(s: &str) -> Option<SpawnRules> {
+    let values: Vec<u8> = s
+        .split_whitespace()
+        .map(str::parse)
+        .collect::<Result<_, _>>()
+        .ok()?;
+
+    let [w1, w2, w3, w4, initial_tiles, tiles_per_turn] = values[..] else {
+        return None;
+    };
+    let weights = [w1, w2, w3, w4];
+    if weights.iter().all(|&weight| weight == 0)
+        || !(1..=SpawnRules::MAX_TILES_PER_TURN).contains(&tiles_per_turn)
+    {
+        return None;
+    }
+
+    Some(
+        SpawnRules::new()
+            .with_weights(weights)
+            .with_initial_tiles(initial_tiles)
+            .with_tiles_per_turn(tiles_per_turn),
+    )
+}
+
+fn parse_turn(s: &str) -> Option<Turn> {
+    let values: Vec<_> = s.split_whitespace().collect();
+    let (&action, values) = values.split_first()?;
+    let (&reward, spawns) = values.split_last()?;
+
+    let spawns: Vec<_> = spawns.chunks(3).map(parse_spawn).collect::<Option<_>>()?;
+    if spawns.is_empty() {
+        return None;
+    }
+
+    Some(Turn {
+        action: parse_direction(action)?,
+        spawns,
+        reward: f32::from_str(reward).ok()?,
+    })
+}
+
+fn parse_spawn(values: &[&str]) -> Option<SpawnedTile> {
+    let &[row, col, exponent] = values else {
+        return None;
+    };
+
+    Some(SpawnedTile {
+        row: row.parse().ok()?,
+        col: col.parse().ok()?,
+        exponent: exponent.parse().ok()?,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{GameRecord, ParseRecordError, RecordWriter, ReplayError, SpawnedTile, Turn};
+    use crate::game::twenty_forty_eight::{
+        board::{Direction, SpawnRules},
+        State,
+    };
+    use crate::game::{GameState as _, Outcome as _};
+    use rand::SeedableRng as _;
+
+    fn play(seed: u64, rules: SpawnRules) -> (GameRecord<4, 4>, String, State<4, 4>) {
+        let mut rng = rand::rngs::StdRng::seed_from_u64(seed);
+        let mut state = State::<4, 4>::new_with_rules(rules, &mut rng);
+        let mut record = GameRecord::new(seed, &state);
+        let mut writer = RecordWriter::new(Vec::new(), seed, &state).unwrap();
+
+        loop {
+            let Some(action) = state.legal_actions().next() else {
+                break;
+            };
+            let (reward, outcome) = state.outcome(action).unwrap();
+            let next = outcome.clone().collapse_with(&mut rng);
+
+            writer.record(action, reward, &outcome, &next).unwrap();
+            record
+                .turns
+                .push(Turn::from_transition(action, reward, &outcome, &next).unwrap());
+            state = next;
+        }
+
+        let text = String::from_utf8(writer.into_inner()).unwrap();
+        (record, text, state)
+    }
+
+    #[test]
+    fn test_round_trip() {
+        let (record, text, last_state) = play(42, SpawnRules::default());
+        assert!(!record.turns.is_empty());
+
+        let mut written = Vec::new();
+        record.write(&mut written).unwrap();
+        assert_eq!(String::from_utf8(written).unwrap(), text);
+
+        let read = GameRecord::<4, 4>::read(text.as_bytes()).unwrap();
+        assert_eq!(read, record);
+        assert_eq!(read.replay(), Ok(last_state));
+
+        assert!(matches!(
+            GameRecord::<3, 3>::read(text.as_bytes()),
+            Err(ParseRecordError::SizeMismatch { cols: 4, rows: 4 })
+        ));
+    }
+
+    #[test]
+    fn test_round_trip_with_rules() {
+        let rules = SpawnRules::new()
+            .with_weights([1, 2, 1, 1])
+            .with_initial_tiles(3);
+        let (record, text, last_state) = play(7, rules);
+        assert!(text.contains("rules 1 2 1 1 3 1"));
+
+        let read = GameRecord::<4, 4>::read(text.as_bytes()).unwrap();
+        assert_eq!(read.rules, rules);
+        assert_eq!(read.replay(), Ok(last_state));
+
+        // The 8s and 16s can't be spawned with the default rules.
+        let default_rules = GameRecord {
+            rules: SpawnRules::default(),
+            ..record
+        };
+        assert!(matches!(
+            default_rules.replay(),
+            Err(ReplayError::IllegalSpawn { .. })
+        ));
+
+        let invalid_rules = text.replace("rules 1 2 1 1 3 1", "rules 0 0 0 0 3 1");
+        assert!(matches!(
+            GameRecord::<4, 4>::read(invalid_rules.as_bytes()),
+            Err(ParseRecordError::InvalidValue { field: "rules", .. })
+        ));
+    }
+
+    #[test]
+    fn test_round_trip_with_spawns_per_turn() {
+        let rules = SpawnRules::new().with_tiles_per_turn(2);
+        let (record, text, last_state) = play(3, rules);
+        assert!(record.turns.iter().any(|turn| turn.spawns.len() == 2));
+
+        let read = GameRecord::<4, 4>::read(text.as_bytes()).unwrap();
+        assert_eq!(read, record);
+        assert_eq!(read.replay(), Ok(last_state));
+
+        // One of the two tiles is missing.
+        let mut missing_spawn = record.clone();
+        let turn = missing_spawn
+            .turns
+            .iter_mut()
+            .find(|turn| turn.spawns.len() == 2)
+            .unwrap();
+        turn.spawns.pop();
+        assert!(matches!(
+            missing_spawn.replay(),
+            Err(ReplayError::IllegalSpawn { .. })
+        ));
+    }
+
+    #[test]
+    fn test_replay_errors() {
+        let text = "
+            # Comments and blank lines are ignored.
+            size 4 4
+            seed 0
+            rules 2 1 0 0 1 1
+            board . . . . / . . . . / . . . . / 1 . . 1
+
+            turn left 0 0 1 4
+        ";
+        let record = GameRecord::<4, 4>::read(text.as_bytes()).unwrap();
+        assert_eq!(record.turns.len(), 1);
+        assert!(record.replay().is_ok());
+
+        let replay_with = |turn: Turn| {
+            let mut record = record.clone();
+            record.turns = vec![turn];
+            record.replay()
+        };
+
+        let turn = record.turns[0].clone();
+        let spawn = turn.spawns[0];
+        let with_spawn = |spawn: SpawnedTile| Turn {
+            spawns: vec![spawn],
+            ..turn.clone()
+        };
+
+        assert!(matches!(
+            replay_with(Turn {
+                action: Direction::Down,
+                ..turn.clone()
+            }),
+            Err(ReplayError::IllegalAction { turn: 0, .. })
+        ));
+        assert!(matches!(
+            replay_with(Turn {
+                reward: 8.0,
+                ..turn.clone()
+            }),
+            Err(ReplayError::RewardMismatch { turn: 0, .. })
+        ));
+        assert_eq!(
+            replay_with(with_spawn(SpawnedTile {
+                exponent: 3,
+                ..spawn
+            })),
+            Err(ReplayError::IllegalSpawn { turn: 0 })
+        );
+        assert_eq!(
+            replay_with(with_spawn(SpawnedTile {
+                row: 3,
+                col: 0,
+                ..spawn
+            })),
+            Err(ReplayError::IllegalSpawn { turn: 0 })
+        );
+        assert_eq!(
+            replay_with(with_spawn(SpawnedTile { col: 4, ..spawn })),
+            Err(ReplayError::IllegalSpawn { turn: 0 })
+        );
+        assert_eq!(
+            replay_with(Turn {
+                spawns: vec![spawn, SpawnedTile { row: 1, ..spawn }],
+                ..turn.clone()
+            }),
+            Err(ReplayError::IllegalSpawn { turn: 0 })
+        );
+
+        let bad_turn = text.replace("turn left", "turn sideways");
+        assert!(matches!(
+            GameRecord::<4, 4>::read(bad_turn.as_bytes()),
+            Err(ParseRecordError::InvalidValue { line: 8, .. })
+        ));
+
+        let trailing_line = format!("{text}\nseed 1");
+        assert!(matches!(
+            GameRecord::<4, 4>::read(trailing_line.as_bytes()),
+            Err(ParseRecordError::MissingField { field: "turn", .. })
+        ));
+    }
+}
@@ -40,16 +40,43 @@ pub fn end_screen() {
     println!("\x1b[?1049l");
 }
 
-pub fn measure_performance() -> f32 {
-    use bots::mean_max::{
-        searcher::{Decision, SearchConstraint},
-        MeanMax,
-    };
-    use game::twenty_forty_eight::State;
+/// Plays a game with the given seed and returns the total reward.
+///
+/// The seed fixes the spawns of the game, the moves depend on how deep the bot gets before the
+/// deadline.
+pub fn measure_performance(seed: u64) -> f32 {
+    record_performance(seed, std::io::sink()).expect("writing to a sink can't fail")
+}
+
+/// Same as [`measure_performance`], but also writes the record of the game to `writer`.
+///
+/// # Errors
+///
+/// Returns an error if writing the record fails.
+pub fn record_performance(seed: u64, writer: impl std::io::Write) -> std::io::Result<f32> {
+    let mut ai = bots::mean_max::MeanMax::new();
+    record_performance_with(&mut ai, seed, writer)
+}
+
+/// Same as [`record_performance`], with the given bot instead of
+/// [`MeanMax`](bots::mean_max::MeanMax).
+///
+/// # Errors
+///
+/// Returns an error if writing the record fails.
+pub fn record_performance_with(
+    ai: &mut (impl bots::Bot<game::twenty_forty_eight::State<4, 4>> + ?Sized),
+    seed: u64,
+    writer: impl std::io::Write,
+) -> std::io::Result<f32> {
+    use bots::mean_max::searcher::{Decision, SearchConstraint};
+    use game::twenty_forty_eight::{record::RecordWriter, State};
+    use rand::SeedableRng as _;
     use std::time;
 
-    let mut game = State::<4, 4>::new();
-    let mut ai = MeanMax::new();
+    let mut rng = rand::rngs::StdRng::seed_from_u64(seed);
+    let mut game = State::<4, 4>::new_with_rng(&mut rng);
+    let mut record = RecordWriter::new(writer, seed, &game)?;
     let search_time = time::Duration::from_secs_f64(0.001);
 
     let mut deadline = time::Instant::now();
@@ -66,15 +93,19 @@ pub fn measure_performance() -> f32 {
             Decision::Resign => break,
         };
 
-        let (reward, outcome) = game.outcome(act.action);
+        let (reward, outcome) = game
+            .outcome(act.action)
+            .expect("the bot only picks legal actions");
         total_reward += reward;
 
-        game = outcome.collapse();
+        let next = outcome.clone().collapse_with(&mut rng);
+        record.record(act.action, reward, &outcome, &next)?;
+        game = next;
         if game.is_terminal() {
             // The game has ended
             break;
         }
     }
 
-    total_reward
+    Ok(total_reward)
 }
@@ -1,15 +1,77 @@
+use rand::SeedableRng as _;
 use rust_2048_solver::{
-    bots::mean_max::{
-        searcher::{Decision, SearchConstraint},
-        MeanMax,
+    bots::{
+        mcts::Mcts,
+        mean_max::{
+            logger::LoggerSettings,
+            searcher::{Decision, SearchConstraint},
+            MeanMax,
+        },
+        Bot,
+    },
+    game::{
+        twenty_forty_eight::{record::RecordWriter, State},
+        GameState, Outcome,
     },
-    game::{twenty_forty_eight::State, GameState, Outcome},
 };
-use std::io::Write;
+use std::fs::File;
+use std::io::{BufWriter, Write};
+use std::path::PathBuf;
 use std::time::{Duration, Instant};
 
+/// Returns the argument that follows `flag`, exits with `usage` if it's missing.
+fn flag_value(flag: &str, usage: &str) -> Option<String> {
+    let mut args = std::env::args().skip(1);
+    while let Some(arg) = args.next() {
+        if arg != flag {
+            continue;
+        }
+
+        return Some(args.next().unwrap_or_else(|| exit_with_usage(usage)));
+    }
+
+    None
+}
+
+fn exit_with_usage(usage: &str) -> ! {
+    eprintln!("Usage: {usage}");
+    std::process::exit(2);
+}
+
+/// Returns the value of `--seed <SEED>`, or a random seed if it's not given.
+fn parse_seed() -> u64 {
+    const USAGE: &str = "--seed <SEED> where SEED is a non-negative integer";
+
+    match flag_value("--seed", USAGE) {
+        Some(seed) => seed.parse().unwrap_or_else(|_| exit_with_usage(USAGE)),
+        None => rand::random(),
+    }
+}
+
+/// Returns the path given with `--record <PATH>`, where the game record should be written.
+fn parse_record_path() -> Option<PathBuf> {
+    flag_value("--record", "--record <PATH>").map(PathBuf::from)
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+enum BotKind {
+    MeanMax,
+    Mcts,
+}
+
+/// Returns the bot chosen with `--bot <BOT>`, [`MeanMax`] if it's not given.
+fn parse_bot() -> BotKind {
+    const USAGE: &str = "--bot <BOT> where BOT is mean-max or mcts";
+
+    match flag_value("--bot", USAGE).as_deref() {
+        None | Some("mean-max") => BotKind::MeanMax,
+        Some("mcts") => BotKind::Mcts,
+        Some(_) => exit_with_usage(USAGE),
+    }
+}
+
 fn main() {
-    // TODO: Add command line arguments.
+    // TODO: Add more command line arguments.
 
     // show_map(heuristic::get_lookup());
 
@@ -19,32 +81,56 @@ fn main() {
         .parse_default_env()
         .init();
 
+    let seed = parse_seed();
+    let bot = parse_bot();
+
     let measure_performance_mode = false;
     if measure_performance_mode {
-        return measure_performance();
+        return measure_performance(seed, bot);
     }
 
-    let mut ai = MeanMax::new();
-
-    {
-        let mut logger = ai.logger.lock().unwrap();
-        logger.log_search_results = true;
-        logger.log_deadline_miss = true;
-        logger.clear_screen = true;
-        logger.print_size_of_critical_structs = false;
-    }
+    log::info!("Seed: {seed}");
+    let mut rng = rand::rngs::StdRng::seed_from_u64(seed);
+
+    let clear_screen = true;
+    let mut ai: Box<dyn Bot<State<4, 4>>> = match bot {
+        BotKind::MeanMax => {
+            let ai = MeanMax::builder()
+                .with_logger_settings(LoggerSettings {
+                    log_search_results: true,
+                    log_deadline_miss: true,
+                    clear_screen,
+                    ..LoggerSettings::default()
+                })
+                .build()
+                .expect("the settings should be valid");
+
+            Box::new(ai)
+        }
+        BotKind::Mcts => Box::new(Mcts::default()),
+    };
 
     let auto_adjust_search_time = true;
     let base_search_time = Duration::from_secs_f64(0.1);
 
     let mut search_time_multiplier = 1;
 
-    if ai.logger.lock().unwrap().clear_screen {
+    if clear_screen {
         rust_2048_solver::init_screen();
     }
 
-    let mut game = State::<4, 4>::new();
+    let mut game = State::<4, 4>::new_with_rng(&mut rng);
     println!("{}", game.cells);
+
+    let mut record = parse_record_path().map(|path| {
+        File::create(&path)
+            .and_then(|file| RecordWriter::new(BufWriter::new(file), seed, &game))
+            .unwrap_or_else(|err| {
+                eprintln!("Failed to create the record {}: {err}", path.display());
+                std::process::exit(1);
+            })
+    });
+
     loop {
         let search_duration = search_time_multiplier * base_search_time;
         let deadline = Instant::now() + search_duration;
@@ -67,8 +153,23 @@ fn main() {
 
         log::info!("Action: {action}", action = act.action);
 
-        let (_reward, outcome) = game.outcome(act.action);
-        game = outcome.collapse();
+        let (reward, outcome) = match game.clone().outcome(act.action) {
+            Ok(transition) => transition,
+            Err(err) => {
+                log::error!("The agent picked an illegal action: {err}");
+                break;
+            }
+        };
+        let next = outcome.clone().collapse_with(&mut rng);
+
+        if let Some(writer) = &mut record {
+            if let Err(err) = writer.record(act.action, reward, &outcome, &next) {
+                log::error!("Failed to write the game record: {err}");
+                record = None;
+            }
+        }
+
+        game = next;
         println!("{}", game.cells);
 
         if game.is_terminal() {
@@ -76,21 +177,20 @@ fn main() {
             break;
         }
 
-        #[allow(clippy::match_overlapping_arm)]
+        // Search longer as the board fills up, the evaluation is a score that only grows.
         if auto_adjust_search_time {
-            search_time_multiplier = match act.eval.value as u32 {
-                ..=20 => 200,
-                ..=50 => 100,
-                ..=100 => 50,
-                ..=200 => 15,
-                ..=500 => 5,
-                ..=1000 => 2,
+            search_time_multiplier = match game.cells.count_empty() {
+                0..=1 => 50,
+                2 => 20,
+                3 => 10,
+                4 => 5,
+                5..=6 => 2,
                 _ => 1,
             };
         }
     }
 
-    if ai.logger.lock().unwrap().clear_screen {
+    if clear_screen {
         rust_2048_solver::end_screen();
     }
 
@@ -99,10 +199,11 @@ fn main() {
     // utils::print_lookup(&ai);
 }
 
-fn measure_performance() {
+fn measure_performance(seed: u64, bot: BotKind) {
     const N_SAMPLES: i32 = 100;
 
-    log::info!("Collecting {N_SAMPLES} samples");
+    // Sample `i` is played with `seed + i`.
+    log::info!("Collecting {N_SAMPLES} samples starting from seed {seed}");
     if log::log_enabled!(log::Level::Info) {
         let total_score: f32 = (0..N_SAMPLES)
             .map(|i| {
@@ -113,7 +214,16 @@ fn measure_performance() {
 
                 std::io::stdout().flush().expect("failed to flush stdout");
 
-                rust_2048_solver::measure_performance()
+                let seed = seed.wrapping_add(i as u64);
+                match bot {
+                    BotKind::MeanMax => rust_2048_solver::measure_performance(seed),
+                    BotKind::Mcts => rust_2048_solver::record_performance_with(
+                        &mut Mcts::default(),
+                        seed,
+                        std::io::sink(),
+                    )
+                    .expect("writing to a sink can't fail"),
+                }
             })
             .sum();
 